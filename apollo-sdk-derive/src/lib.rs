@@ -0,0 +1,146 @@
+//! `#[derive(ApolloConfig)]`, 生成 `apollo_sdk::client::declare::ApolloConfig` 的实现.
+//! 通过 apollo-sdk 的 `derive` feature 使用, 属性说明见 `apollo_sdk::client::declare`
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, spanned::Spanned, Data, DeriveInput, Fields, GenericArgument, Lit, LitStr, PathArguments, Type};
+
+#[proc_macro_derive(ApolloConfig, attributes(apollo))]
+pub fn derive_apollo_config(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+#[derive(Default)]
+struct ApolloAttr {
+    namespace: Option<LitStr>,
+    key: Option<LitStr>,
+    default: Option<String>,
+    validate: Option<syn::Path>,
+    on_reload: Option<syn::Path>,
+}
+
+fn parse_attrs(attrs: &[syn::Attribute], is_field: bool) -> syn::Result<ApolloAttr> {
+    let mut res = ApolloAttr::default();
+    for attr in attrs {
+        if !attr.path().is_ident("apollo") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("namespace") {
+                res.namespace = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("validate") {
+                let path: LitStr = meta.value()?.parse()?;
+                res.validate = Some(path.parse()?);
+            } else if is_field && meta.path.is_ident("key") {
+                res.key = Some(meta.value()?.parse()?);
+            } else if is_field && meta.path.is_ident("default") {
+                let lit: Lit = meta.value()?.parse()?;
+                let default = match &lit {
+                    Lit::Str(s) => s.value(),
+                    Lit::Int(i) => i.base10_digits().to_string(),
+                    Lit::Float(f) => f.base10_digits().to_string(),
+                    Lit::Bool(b) => b.value.to_string(),
+                    Lit::Char(c) => c.value().to_string(),
+                    _ => return Err(syn::Error::new(lit.span(), "unsupported default literal")),
+                };
+                res.default = Some(default);
+            } else if !is_field && meta.path.is_ident("on_reload") {
+                let path: LitStr = meta.value()?.parse()?;
+                res.on_reload = Some(path.parse()?);
+            } else {
+                return Err(meta.error("unknown apollo attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(res)
+}
+
+/// `Option<T>` 返回 T
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else { return None };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else { return None };
+    match args.args.first()? {
+        GenericArgument::Type(inner) => Some(inner),
+        _ => None,
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(syn::Error::new(input.span(), "ApolloConfig only supports structs with named fields")),
+        },
+        _ => return Err(syn::Error::new(input.span(), "ApolloConfig only supports structs")),
+    };
+    let struct_attr = parse_attrs(&input.attrs, false)?;
+
+    let mut loads = Vec::new();
+    let mut idents = Vec::new();
+    let mut keys = Vec::new();
+    for field in fields {
+        let ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let attr = parse_attrs(&field.attrs, true)?;
+
+        let key = attr.key.map(|k| k.value()).unwrap_or_else(|| ident.to_string());
+        let namespace = match attr.namespace.or(struct_attr.namespace.clone()) {
+            Some(ns) => quote!(::std::option::Option::Some(#ns)),
+            None => quote!(::std::option::Option::None),
+        };
+        let default = match attr.default {
+            Some(d) => quote!(::std::option::Option::Some(#d)),
+            None => quote!(::std::option::Option::None),
+        };
+        let load = match option_inner(ty) {
+            Some(inner) => quote!(::apollo_sdk::client::declare::optional::<#inner>(client, #namespace, #key, #default)?),
+            None => quote!(::apollo_sdk::client::declare::required::<#ty>(client, #namespace, #key, #default)?),
+        };
+        let validate = attr.validate.map(|path| quote!(::apollo_sdk::client::declare::validate_field(#key, &#ident, #path)?;));
+        loads.push(quote! {
+            let #ident: #ty = #load;
+            #validate
+        });
+        idents.push(ident);
+        keys.push(quote!((#namespace, #key)));
+    }
+
+    let validate = struct_attr.validate.map(|path| quote! {
+        fn validate(&self) -> ::std::result::Result<(), ::std::string::String> {
+            #path(self)
+        }
+    });
+    let on_reload = struct_attr.on_reload.map(|path| quote! {
+        fn reloaded(&self, old: &Self) {
+            #path(self, old)
+        }
+    });
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::apollo_sdk::client::declare::ApolloConfig for #name #ty_generics #where_clause {
+            fn load_fields(client: &::apollo_sdk::client::apollo_config_client::ApolloConfigClient) -> ::std::result::Result<Self, ::apollo_sdk::client::error::ApolloError> {
+                #(#loads)*
+                ::std::result::Result::Ok(Self { #(#idents),* })
+            }
+
+            fn keys() -> ::std::vec::Vec<(::std::option::Option<&'static str>, &'static str)> {
+                ::std::vec![#(#keys),*]
+            }
+
+            #validate
+            #on_reload
+        }
+    })
+}
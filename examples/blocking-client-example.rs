@@ -0,0 +1,25 @@
+//同步接口, 不需要自己创建tokio runtime. 需要开启 blocking feature
+#![allow(clippy::unnecessary_unwrap)]
+
+fn main() {
+    let meta = vec!["http://1.2.3.4:8080"];
+    let acc = apollo_sdk::client::blocking::new(meta, "SampleApp", "DEV", Some(vec!["application"]), None);
+    if acc.is_err() {
+        panic!("can not connect apollo server....error:{:?}", acc.err());
+    }
+    let acc = acc.unwrap();
+
+    let err = acc.listen_namespace("ns2");
+    if err.is_some() {
+        println!("listen namespace failed: {}", err.unwrap());
+    }
+    println!("testKey: {:?}", acc.get_config("testKey"));
+
+    loop {
+        let events = acc.fetch_change_event();
+        if events.is_some() {
+            println!("config changed: {:?}", events.unwrap());
+        }
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
@@ -1,4 +1,7 @@
 //config client 提供基础的配置拉取/监听功能
+#![allow(clippy::unnecessary_unwrap)]
+
+use apollo_sdk::client::signer::AccessKeySource;
 
 #[tokio::main]
 async fn main() {
@@ -6,7 +9,6 @@ async fn main() {
     let app_id = "SampleApp";
     let cluster = "DEV";
     let ns = Some(vec!["application", "ns2"]);
-    let secret = Some("....secret....");
 
     //初始化时可以不监听任何namespace  
     let acc = apollo_sdk::client::apollo_config_client::new(meta, app_id, cluster, ns, None).await;
@@ -29,12 +31,35 @@ async fn main() {
         println!("config key:{}, config value: {}, from namespace: {}", value.config_key, value.config_value, value.namespace);
     }
 
+    //环境变量 APP_DB_HOST 覆盖 db.host, 本地文件覆盖Apollo配置, 返回的配置项中 layer 标明来源
+    acc.set_env_override(Some("APP_"));
+    let _ = acc.set_override_file(Some("./apollo-override.properties"));
+
+    //开启占位符解析 如 url = jdbc://${db.host}:${db.port:3306}
+    acc.enable_placeholder(true);
+
+    //列出所有可用的key, 按前缀获取配置
+    println!("all keys: {:?}", acc.keys());
+    for (key, item) in acc.get_by_prefix("db.") {
+        println!("config key:{}, config value: {}, from namespace: {}", key, item.config_value, item.namespace);
+    }
+
+    //access key轮换: 新旧secret同时配置, 签名校验失败时自动尝试下一个
+    acc.set_access_keys(AccessKeySource::Static(vec!["new-secret".to_string(), "old-secret".to_string()]));
+
+    //namespace不存在时作为空namespace监听, 创建并发布后自动生效
+    acc.tolerate_missing_namespace(true);
+
     //追加监听一个新的namespace  如果已经被监听过  直接返回
     let listen_res = acc.listen_namespace("ns2").await;
     if listen_res.is_some() {
         panic!("fail to listening namespace....error:{:?}", listen_res.unwrap());
     }
 
+    //显式指定优先级 优先级高的namespace配置优先生效
+    acc.set_namespace_priority("application", 10);
+    println!("namespace resolution order: {:?}", acc.namespace_order());
+
     std::thread::sleep(std::time::Duration::from_secs(30));
 
     let change = acc.fetch_change_event();
@@ -43,5 +68,11 @@ async fn main() {
         println!("change event: {:?}", event);
     }
 
+    //查看各namespace的releaseKey, 最近一次拉取时间和错误
+    println!("client status: {:?}", acc.status());
+
+    //取消监听namespace 其配置从缓存中移除
+    acc.unlisten_namespace("ns2");
+
     acc.close();
 }
\ No newline at end of file
@@ -1,5 +1,6 @@
 //openapi client 提供Apollo 开放平台接口操作能力
 //接口信息参考 https://www.apolloconfig.com/#/zh/usage/apollo-open-api-platform?id=%e4%b8%89%e3%80%81-%e6%8e%a5%e5%8f%a3%e6%96%87%e6%a1%a3
+#![allow(clippy::unnecessary_unwrap)]
 
 #[tokio::main]
 async fn main() {
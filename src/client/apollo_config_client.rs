@@ -1,23 +1,49 @@
-use std::{collections::HashMap, sync::{Mutex, Arc}, thread};
+// 沿用 is_err()/unwrap() 和显式 return 的写法
+#![allow(
+    clippy::unnecessary_unwrap,
+    clippy::needless_return,
+    clippy::len_zero,
+    clippy::question_mark,
+    clippy::let_and_return,
+    clippy::type_complexity,
+)]
+
+use std::{collections::HashMap, sync::{Condvar, Mutex, Arc}, thread, time::SystemTime};
 
-use crypto::mac::Mac;
 use log::{info, debug};
 use serde::{Deserialize, Serialize};
 
-use super::{meta_server::{MetaServer, ApolloServerEnum}, error::ApolloError};
+use super::{metrics, trace::{record_field, trace_event}, meta_server::{MetaServer, ApolloServerEnum}, error::ApolloError, placeholder::{apollo_resolve_placeholder, has_placeholder}, layer::{OverrideFile, env_override}, signer::{ApolloSigner, AccessKeySource, RequestSigner}};
 
-/// 包含四个元素: 实际的client, 配置缓存, close signal sender channel, config change events receiver channel
+/// 包含四个元素: 实际的client, 配置缓存, close signal sender channel, config change events receiver channel.
+/// clone 得到的是同一个client的句柄, 共享缓存和`fetch_change_event`的事件队列
+#[derive(Clone)]
 pub struct ApolloConfigClient (Arc<Mutex<(apollo_config_client, config_cache, tokio::sync::watch::Sender<bool>, tokio::sync::broadcast::Receiver<Vec<ApolloChangeEvent>>)>>);
 
 #[allow(non_camel_case_types)]
 type config_cache = Vec<Arc<Mutex<apollo_namespace>>>;
 
 /// 获取到的配置项
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ApolloConfigItem {
     pub config_key: String,
     pub config_value: String,
+    /// 来源为Apollo时是namespace, 本地覆盖文件时是文件路径, 环境变量时是环境变量名
     pub namespace: String,
+    pub layer: ApolloConfigLayer,
+}
+
+/// 配置来源, 优先级从高到低: 环境变量, 本地覆盖文件, Apollo namespace
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ApolloConfigLayer {
+    Env,
+    OverrideFile,
+    Apollo,
+    /// `source::StaticSource` 中的配置
+    Static,
+    /// `source::PropertiesFileSource` 读取的配置文件
+    File,
 }
 
 #[allow(non_camel_case_types)]
@@ -26,9 +52,57 @@ struct apollo_config_client {
     config_srv_list: Vec<String>,
     app_id_default: String,
     cluster_default: String,
-    secret: String,
+    signer: ApolloSigner,
+    change_event_tx: tokio::sync::broadcast::Sender<Vec<ApolloChangeEvent>>,
+    rejection_tx: tokio::sync::broadcast::Sender<ApolloReleaseRejection>,
+    /// 各namespace自身配置的变更, 见`subscribe_namespaces`
+    namespace_event_tx: tokio::sync::broadcast::Sender<Vec<ApolloChangeEvent>>,
+    /// 不连接服务端(`new_offline`), 监听namespace时直接作为空namespace监听
+    offline: bool,
+    last_long_poll: Option<SystemTime>,
+    last_long_poll_error: Option<String>,
+    placeholder: bool,
+    tolerate_not_found: bool,
+    closed: bool,
+    env_prefix: Option<String>,
+    override_file: Option<OverrideFile>,
+    /// 开启访问统计时为 Some, key 为 (指定的namespace, key)
+    access: Option<HashMap<(Option<String>, String), ApolloKeyAccess>>,
+    lazy_load: ApolloLazyLoad,
+    /// 按需加载中(None)或加载失败时间的namespace
+    lazy_pending: HashMap<String, Option<std::time::Instant>>,
+    /// 按需加载结束时通知Wait模式下等待的读取
+    lazy_done: Arc<Condvar>,
+    /// 监听线程的runtime, 按需加载在其中执行. 离线client为None
+    runtime: Option<tokio::runtime::Handle>,
+    /// 放弃进行中的长轮询, 立即按新的namespace和notificationId重新发起
+    poll_restart: Arc<tokio::sync::Notify>,
+    /// (namespace, validator), namespace为None时校验所有namespace
+    validators: Vec<(Option<String>, ApolloReleaseValidator)>,
+}
+
+/// 校验新发布的配置, 返回Err时拒绝该发布, 见`add_release_validator`
+pub type ApolloReleaseValidator = Arc<dyn Fn(&ApolloRelease) -> Result<(), String> + Send + Sync>;
+
+/// 待校验的新发布
+#[derive(Debug)]
+pub struct ApolloRelease<'a> {
+    pub namespace: &'a str,
+    pub release_key: &'a str,
+    pub configurations: &'a HashMap<String, String>,
 }
 
+/// 被validator拒绝的发布, 见`subscribe_rejections`
+#[derive(Debug, Clone)]
+pub struct ApolloReleaseRejection {
+    pub namespace: String,
+    pub release_key: String,
+    pub reason: String,
+}
+
+/// 按需加载失败的namespace, 间隔多久后再次尝试
+const LAZY_RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
 #[allow(non_camel_case_types)]
 #[derive(Serialize, Deserialize, Debug)]
 struct apollo_namespace {
@@ -43,6 +117,23 @@ struct apollo_namespace {
     
     #[serde(skip_deserializing)]
     notification_id: i32,
+    #[serde(skip_deserializing)]
+    priority: i32,
+    /// 最近一次通知中的messages, 拉取配置和长轮询时带上
+    #[serde(skip)]
+    messages: Option<notification_messages>,
+    #[serde(skip)]
+    last_fetch: Option<SystemTime>,
+    #[serde(skip)]
+    last_error: Option<String>,
+    #[serde(skip)]
+    source: ApolloNamespaceSource,
+    /// 最近一次被validator拒绝的release key, 再次拉取到时不重复校验
+    #[serde(skip)]
+    rejected_release_key: Option<String>,
+    /// 冻结时只接受该release key的发布, 见`freeze_namespace`
+    #[serde(skip)]
+    frozen_release_key: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -52,14 +143,119 @@ struct notification_item {
     namespace: String,
     #[serde(rename = "notificationId")]
     notification_id: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    messages: Option<notification_messages>,
+}
+
+/// ApolloNotificationMessages, details: {appId+cluster+namespace: notificationId}
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[allow(non_camel_case_types)]
+struct notification_messages {
+    details: HashMap<String, i64>,
+}
+
+impl notification_messages {
+    /// 合并新的messages, 同一个key保留较大的notificationId
+    fn merge(&mut self, other: &notification_messages) {
+        for (key, id) in &other.details {
+            let old = self.details.get(key);
+            if old.is_none() || old.unwrap() < id {
+                self.details.insert(key.clone(), *id);
+            }
+        }
+    }
+}
+
+/// config client 的运行状态, 用于判断配置是否过期
+#[derive(Debug, Clone)]
+pub struct ApolloClientStatus {
+    pub app_id: String,
+    pub cluster: String,
+    pub config_servers: Vec<String>,
+    /// 最近一次成功的长轮询(有变化或超时无变化)
+    pub last_long_poll: Option<SystemTime>,
+    pub last_long_poll_error: Option<String>,
+    pub closed: bool,
+    /// 按 `get_config` 的查找顺序排列
+    pub namespaces: Vec<ApolloNamespaceStatus>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ApolloNamespaceStatus {
+    pub namespace: String,
+    pub priority: i32,
+    pub release_key: String,
+    pub notification_id: i32,
+    /// 最近一次成功拉取配置(包括304无变化)的时间
+    pub last_fetch: Option<SystemTime>,
+    /// 最近一次拉取失败的错误, 拉取成功后清空
+    pub last_error: Option<String>,
+    pub source: ApolloNamespaceSource,
+    /// 被validator拒绝的release key, 此时仍使用release_key对应的配置
+    pub rejected_release_key: Option<String>,
+    /// 冻结的release key, 见`freeze_namespace`
+    pub frozen_release_key: Option<String>,
+}
+
+/// 一个key的读取次数, namespace为None表示通过`get_config`读取
+#[derive(Debug, Clone, Serialize)]
+pub struct ApolloKeyAccess {
+    pub namespace: Option<String>,
+    pub key: String,
+    /// 取到值的次数
+    pub hits: u64,
+    /// 没有取到值的次数
+    pub misses: u64,
+}
+
+/// 配置读取情况, 见`enable_access_tracking`
+#[derive(Debug, Clone, Serialize)]
+pub struct ApolloAccessReport {
+    /// 所有被读取过的key, 按namespace和key排序
+    pub accessed: Vec<ApolloKeyAccess>,
+    /// 缓存中存在但从未被读取的 (namespace, key)
+    pub unused: Vec<(String, String)>,
+    /// 读取时至少有一次不存在的key
+    pub missing: Vec<ApolloKeyAccess>,
+}
+
+impl ApolloAccessReport {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+/// 当前缓存中namespace配置的来源
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ApolloNamespaceSource {
+    /// 从config server拉取
+    #[default]
+    Remote,
+    /// namespace不存在, 作为空namespace监听(见`tolerate_missing_namespace`)
+    NotFound,
+    /// 启动时加载失败的可选namespace, 作为空namespace监听(见`new_with_optional_namespaces`)
+    Unavailable,
+}
+
+/// `get_config_from_namespace`读取未监听的namespace时的处理方式, 见`set_lazy_load`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApolloLazyLoad {
+    /// 不加载, 返回None
+    Disabled,
+    /// 在监听线程的runtime中监听namespace, 本次读取返回None
+    Background,
+    /// 同Background, 读取时阻塞当前线程最多指定时间等待首次加载完成.
+    /// 会阻塞调用方所在的async runtime工作线程, 在async代码中使用时应放到`spawn_blocking`中
+    Wait(std::time::Duration),
 }
 
 #[derive(Debug, Clone)]
 pub struct ApolloChangeEvent {
-    namespace: String,
-    key: String,
-    new_value: String,
-    action: ApolloChangeAction,
+    pub namespace: String,
+    pub key: String,
+    pub new_value: String,
+    pub action: ApolloChangeAction,
 }
 
 #[derive(Debug, Clone)]
@@ -71,6 +267,12 @@ pub enum ApolloChangeAction {
 
 
 pub async fn new(meta_server: Vec<&str>, app_id: &str, cluster_name: &str, namespaces: Option<Vec<&str>>, secret: Option<&str>) -> Result<ApolloConfigClient, ApolloError> {
+    new_with_optional_namespaces(meta_server, app_id, cluster_name, namespaces.unwrap_or_default(), Vec::new(), secret).await
+}
+
+/// 同`new`, required中的namespace加载失败时返回错误, optional中的namespace加载失败时只记录日志,
+/// 作为空namespace监听(status中source为`Unavailable`), 之后由长轮询重新拉取
+pub async fn new_with_optional_namespaces(meta_server: Vec<&str>, app_id: &str, cluster_name: &str, required: Vec<&str>, optional: Vec<&str>, secret: Option<&str>) -> Result<ApolloConfigClient, ApolloError> {
     let ms = MetaServer::new(meta_server);
     let config_srvs = ms.get_config_servers(ApolloServerEnum::ConfigServer).await;
     if config_srvs.is_err() {
@@ -78,225 +280,817 @@ pub async fn new(meta_server: Vec<&str>, app_id: &str, cluster_name: &str, names
     }
     let config_srvs = config_srvs.unwrap();
     if config_srvs.len() == 0 {
-        return Err(ApolloError::new(111, "no valid config server address".to_string()));
+        return Err(ApolloError::Discovery("no valid config server address".to_string()));
     }
 
-    let sign = secret.unwrap_or_default();
-    let cc = apollo_config_client{
-        meta_server: ms,
-        config_srv_list: config_srvs,
-        app_id_default: app_id.to_string(),
-        cluster_default: cluster_name.to_string(),
-        secret: sign.to_string(),
-    };
-    
+    let (apc, close_rx) = apollo_client_new(ms, config_srvs, app_id, cluster_name, secret);
+    let apc_2 = ApolloConfigClient(apc.0.clone());
 
-    let ns_filter = |nss: Option<Vec<&str>>| -> Option<Vec<String>> {
-        if nss.is_none() {
-            return None;
-        }
-        let tmp = nss.unwrap();
+    let ns_filter = |nss: Vec<&str>| -> Vec<String> {
         let mut ns: Vec<String> = Vec::new();
-        for ele in tmp {
+        for ele in nss {
             if !ns.contains(&ele.to_string()) {
                 ns.push(ele.to_string());
             }
         }
-        return Some(ns);
+        return ns;
     };
 
-    let (close_tx, close_rx) = tokio::sync::watch::channel(false);
-    let (change_event_tx, cheange_event_rx) = tokio::sync::broadcast::channel(10);
-
-    let cc_arc = Arc::new(Mutex::new((cc, Vec::new(), close_tx, cheange_event_rx)));
-    let cc_arc_clone = cc_arc.clone();
-    let apc = ApolloConfigClient(cc_arc);
-    let apc_2 = ApolloConfigClient(cc_arc_clone);
-
-    let ns = ns_filter(namespaces);
-    debug!("listen namespace {:?} when initial config client", ns);
-    if ns.is_some() {
-        let ns = ns.unwrap();
-        for ele in ns {
-            let err = apc.listen_namespace(&ele).await;
-            if err.is_some() {
-                let ae = err.unwrap();
-                log::error!("listen namespace error: {:?}", &ae);
-                return Err(ae);
-            }
+    let required = ns_filter(required);
+    let optional: Vec<String> = ns_filter(optional).into_iter().filter(|ns| !required.contains(ns)).collect();
+    debug!("listen namespace {:?}, optional {:?} when initial config client", required, optional);
+    for ele in required {
+        let err = apc.listen_namespace(&ele).await;
+        if err.is_some() {
+            let ae = err.unwrap();
+            log::error!("listen namespace error: {:?}", &ae);
+            return Err(ae);
+        }
+    }
+    for ele in optional {
+        let err = apc.listen_namespace(&ele).await;
+        if err.is_some() {
+            let ae = err.unwrap();
+            log::warn!("listen optional namespace {} failed, listen it as an empty namespace. error: {:?}", &ele, &ae);
+            let mut apc = apc.0.lock().unwrap();
+            let source = match ae {
+                ApolloError::NamespaceNotFound { .. } => ApolloNamespaceSource::NotFound,
+                _ => ApolloNamespaceSource::Unavailable,
+            };
+            let an = apollo_empty_namespace(&apc.0, &ele, ae.to_string(), source);
+            apollo_cache_insert(&mut apc.1, an);
         }
     }
     
+    //runtime在监听线程中drop, 不能在async上下文中drop
+    let rt = tokio::runtime::Runtime::new();
+    if rt.is_err() {
+        return Err(ApolloError::Io(rt.unwrap_err()));
+    }
+    let rt = rt.unwrap();
+    apc.0.lock().unwrap().0.runtime = Some(rt.handle().clone());
     let _ =thread::spawn(move || {
-        let rt = tokio::runtime::Runtime::new().unwrap();
-        rt.block_on(apc_2.loop_listening(close_rx, change_event_tx));
+        rt.block_on(apc_2.loop_listening(close_rx));
     });
 
     return Ok(apc); 
 }
 
+/// 加载失败时监听的空namespace, 长轮询通知后重新拉取
+fn apollo_empty_namespace(cc: &apollo_config_client, namespace: &str, error: String, source: ApolloNamespaceSource) -> apollo_namespace {
+    apollo_namespace {
+        app_id: cc.app_id_default.clone(),
+        cluster: cc.cluster_default.clone(),
+        namespace: namespace.to_string(),
+        release_key: "".to_string(),
+        configurations: HashMap::new(),
+        notification_id: -1,
+        priority: 0,
+        messages: None,
+        last_fetch: None,
+        last_error: Some(error),
+        source,
+        rejected_release_key: None,
+        frozen_release_key: None,
+    }
+}
+
+/// 创建client, 不加载namespace也不启动监听
+fn apollo_client_new(ms: MetaServer, config_srvs: Vec<String>, app_id: &str, cluster_name: &str, secret: Option<&str>) -> (ApolloConfigClient, tokio::sync::watch::Receiver<bool>) {
+    let (close_tx, close_rx) = tokio::sync::watch::channel(false);
+    let (change_event_tx, cheange_event_rx) = tokio::sync::broadcast::channel(10);
+
+    let cc = apollo_config_client{
+        meta_server: ms,
+        config_srv_list: config_srvs,
+        app_id_default: app_id.to_string(),
+        cluster_default: cluster_name.to_string(),
+        signer: ApolloSigner::new(secret),
+        change_event_tx,
+        rejection_tx: tokio::sync::broadcast::channel(10).0,
+        namespace_event_tx: tokio::sync::broadcast::channel(10).0,
+        offline: false,
+        last_long_poll: None,
+        last_long_poll_error: None,
+        placeholder: false,
+        tolerate_not_found: false,
+        closed: false,
+        env_prefix: None,
+        override_file: None,
+        access: None,
+        lazy_load: ApolloLazyLoad::Disabled,
+        lazy_pending: HashMap::new(),
+        lazy_done: Arc::new(Condvar::new()),
+        poll_restart: Arc::new(tokio::sync::Notify::new()),
+        runtime: None,
+        validators: Vec::new(),
+    };
+
+    let apc = ApolloConfigClient(Arc::new(Mutex::new((cc, Vec::new(), close_tx, cheange_event_rx))));
+    (apc, close_rx)
+}
+
+/// 不连接服务端的client, 配置通过`apply_release`写入. 返回的receiver需要保持存活, 否则`close`时无法清空缓存
+#[cfg(any(test, feature = "testing"))]
+pub(crate) fn new_offline(app_id: &str, cluster_name: &str) -> (ApolloConfigClient, tokio::sync::watch::Receiver<bool>) {
+    let (apc, close_rx) = apollo_client_new(MetaServer::new(Vec::new()), Vec::new(), app_id, cluster_name, None);
+    apc.0.lock().unwrap().0.offline = true;
+    (apc, close_rx)
+}
+
 impl ApolloConfigClient {
-    ///获取配置项，后监听的namespace优先级更高
+    ///获取配置项，依次从环境变量, 本地覆盖文件(如已开启)和优先级最高的namespace开始查找, 优先级相同时后监听的namespace优先
     pub fn get_config(&self, key: &str) -> Option<ApolloConfigItem> {
-        let cache = &self.0.lock().unwrap().1;
-        let mut idx = cache.len() as i32 - 1;
+        let mut apc = self.0.lock().unwrap();
+        let item = apollo_lookup(&apc.0, &apc.1, key);
+        apollo_record_access(&mut apc.0, None, key, item.is_some());
+        if item.is_none() {
+            return None;
+        }
+        let item = item.unwrap();
+        let value = apollo_resolve_value(&apc.0, &apc.1, item.config_value.clone());
+        Some(ApolloConfigItem{
+            config_value: value,
+            ..item
+        })
+    }
+
+    /// 只从指定namespace取值, 若开启了占位符解析, 占位符仍按所有namespace的优先级解析
+    pub fn get_config_from_namespace(&self, key: &str, namespace: &str) -> Option<ApolloConfigItem> {
+        self.namespace_lookup(key, namespace, true)
+    }
 
-        while idx >= 0 {
-            let ele = cache.get(idx as usize);
-            idx -= 1;
-            if ele.is_none() {
+    /// 同`get_config_from_namespace`, 但不计入读取统计. 供整体读取namespace的适配器使用
+    #[cfg(any(feature = "config", feature = "figment"))]
+    pub(crate) fn get_config_from_namespace_untracked(&self, key: &str, namespace: &str) -> Option<ApolloConfigItem> {
+        self.namespace_lookup(key, namespace, false)
+    }
+
+    fn namespace_lookup(&self, key: &str, namespace: &str, track: bool) -> Option<ApolloConfigItem> {
+        self.lazy_listen(namespace);
+        let mut apc = self.0.lock().unwrap();
+        let mut value = None;
+        for ele in &apc.1 {
+            let an = ele.lock().unwrap();
+            if an.namespace == namespace && an.configurations.contains_key(key) {
+                value = Some(an.configurations.get(key).unwrap().to_string());
                 break;
             }
-            let ele = ele.unwrap();
-            let an = ele.lock().unwrap();
+        }
+        if track {
+            apollo_record_access(&mut apc.0, Some(namespace), key, value.is_some());
+        }
+        if value.is_none() {
+            return None;
+        }
+        let item = ApolloConfigItem {
+            config_key: key.to_string(),
+            config_value: apollo_resolve_value(&apc.0, &apc.1, value.unwrap()),
+            namespace: namespace.to_string(),
+            layer: ApolloConfigLayer::Apollo,
+        };
+        return Some(item);
+    }
 
-            let value = an.configurations.get(key);
+    /// 注册新发布的校验, namespace为None时校验所有namespace. 长轮询拉取到新发布后, 替换缓存前依次执行,
+    /// 任一validator返回Err时保留当前配置, 在status中记录被拒绝的release key和原因, 并通知`subscribe_rejections`的订阅者.
+    /// 同一个release key不会重复校验, 直到有新的发布. validator在监听线程中执行, 不能调用client的方法
+    pub fn add_release_validator<F>(&self, namespace: Option<&str>, validator: F)
+    where
+        F: Fn(&ApolloRelease) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.0.lock().unwrap().0.validators.push((namespace.map(|ns| ns.to_string()), Arc::new(validator)));
+    }
+
+    /// 校验一个key的新值, key在新发布中不存在时不校验, 其他同`add_release_validator`
+    pub fn add_key_validator<F>(&self, namespace: Option<&str>, key: &str, validator: F)
+    where
+        F: Fn(&str) -> Result<(), String> + Send + Sync + 'static,
+    {
+        let key = key.to_string();
+        self.add_release_validator(namespace, move |release| {
+            let value = release.configurations.get(&key);
             if value.is_none() {
+                return Ok(());
+            }
+            validator(value.unwrap()).map_err(|err| format!("{}: {}", key, err))
+        });
+    }
+
+    /// 冻结namespace, 之后的发布被跳过, 用于故障期间让单个实例停止跟随发布. release_key为None时冻结在当前的release key.
+    /// 配置服务只能拉取最新发布, 指定的release key不是当前的release key时, 立即重新拉取一次, 之后跳过其他发布,
+    /// 直到该release key成为最新发布(如回滚到该版本)时应用并停留在该版本. namespace未被监听时返回false.
+    /// 客户端没有磁盘缓存, 冻结状态不会持久化, 重启后需要重新冻结
+    pub fn freeze_namespace(&self, namespace: &str, release_key: Option<&str>) -> bool {
+        let apc = self.0.lock().unwrap();
+        for ele in &apc.1 {
+            let mut an = ele.lock().unwrap();
+            if an.namespace != namespace {
                 continue;
             }
-            let item = ApolloConfigItem{
-                config_key: key.to_string(),
-                config_value: value.unwrap().to_string(),
-                namespace: an.namespace.clone(),
+            let frozen = release_key.map(|rk| rk.to_string()).unwrap_or_else(|| an.release_key.clone());
+            info!("freeze namespace {} at release {}, current release {}", namespace, &frozen, &an.release_key);
+            if frozen != an.release_key {
+                //之前跳过的发布可能就是指定的版本, 重置notificationId重新拉取
+                an.notification_id = -1;
+                apc.0.poll_restart.notify_one();
+            }
+            an.frozen_release_key = Some(frozen);
+            return true;
+        }
+        false
+    }
+
+    /// 解除冻结, 立即重新发起长轮询拉取最新发布. namespace未被监听或未冻结时返回false
+    pub fn unfreeze_namespace(&self, namespace: &str) -> bool {
+        let apc = self.0.lock().unwrap();
+        for ele in &apc.1 {
+            let mut an = ele.lock().unwrap();
+            if an.namespace == namespace {
+                if an.frozen_release_key.is_none() {
+                    return false;
+                }
+                info!("unfreeze namespace {}", namespace);
+                an.frozen_release_key = None;
+                //冻结期间跳过的发布已更新了notificationId, 重置后长轮询会立即返回
+                an.notification_id = -1;
+                apc.0.poll_restart.notify_one();
+                return true;
+            }
+        }
+        false
+    }
+
+    /// namespace已冻结且release_key不是冻结的release key时跳过, notification_id不为None时更新, 避免重复拉取
+    fn skip_frozen(&self, namespace: &str, release_key: &str, notification_id: Option<i32>) -> bool {
+        let apc = self.0.lock().unwrap();
+        for ele in &apc.1 {
+            let mut an = ele.lock().unwrap();
+            if an.namespace != namespace || an.frozen_release_key.is_none() || an.frozen_release_key.as_deref() == Some(release_key) {
+                continue;
+            }
+            debug!("namespace {} is frozen at release {:?}, skip release {}", namespace, &an.frozen_release_key, release_key);
+            if notification_id.is_some() {
+                an.notification_id = notification_id.unwrap();
+            }
+            an.last_fetch = Some(SystemTime::now());
+            return true;
+        }
+        false
+    }
+
+    /// 执行validator, 未通过时记录被拒绝的release key并通知订阅者. notification_id不为None时更新, 避免重复拉取
+    fn check_release(&self, namespace: &str, release_key: &str, configurations: &HashMap<String, String>, notification_id: Option<i32>) -> bool {
+        let (validators, rejected) = {
+            let apc = self.0.lock().unwrap();
+            let validators: Vec<ApolloReleaseValidator> = apc.0.validators.iter()
+                .filter(|(ns, _)| ns.is_none() || ns.as_deref() == Some(namespace))
+                .map(|(_, validator)| validator.clone())
+                .collect();
+            let rejected = apc.1.iter().find_map(|ele| {
+                let an = ele.lock().unwrap();
+                if an.namespace == namespace { an.rejected_release_key.clone() } else { None }
+            });
+            (validators, rejected)
+        };
+        if rejected.as_deref() == Some(release_key) {
+            debug!("release {} of namespace {} has been rejected, skip it", release_key, namespace);
+            if notification_id.is_some() {
+                self.update_namespace(namespace, |an| an.notification_id = notification_id.unwrap());
+            }
+            return false;
+        }
+
+        let release = ApolloRelease { namespace, release_key, configurations };
+        let res = validators.iter().map(|validator| validator(&release)).find(|res| res.is_err());
+        if res.is_none() {
+            return true;
+        }
+        let message = res.unwrap().unwrap_err();
+        log::error!("release {} of namespace {} rejected, keep the current config. reason: {}", release_key, namespace, &message);
+        metrics::counter("apollo_release_rejected_total", &[("namespace", namespace)], 1);
+        self.update_namespace(namespace, |an| {
+            if notification_id.is_some() {
+                an.notification_id = notification_id.unwrap();
+            }
+            an.last_fetch = Some(SystemTime::now());
+            an.last_error = Some(format!("release {} rejected: {}", release_key, message));
+            an.rejected_release_key = Some(release_key.to_string());
+        });
+        let rejection = ApolloReleaseRejection {
+            namespace: namespace.to_string(),
+            release_key: release_key.to_string(),
+            reason: message,
+        };
+        let _ = self.0.lock().unwrap().0.rejection_tx.send(rejection);
+        false
+    }
+
+    /// 开启后`get_config_from_namespace`读取未监听的namespace时以优先级0监听该namespace, 不必在`new`时列出所有namespace.
+    /// 加载失败的namespace在30秒内不再尝试, 配合`tolerate_missing_namespace`可将不存在的namespace作为空namespace监听
+    pub fn set_lazy_load(&self, mode: ApolloLazyLoad) {
+        let mut apc = self.0.lock().unwrap();
+        apc.0.lazy_load = mode;
+        apc.0.lazy_pending.clear();
+    }
+
+    /// namespace未监听时按`lazy_load`的设置在监听线程的runtime中监听, Wait模式下阻塞等待加载完成或失败
+    fn lazy_listen(&self, namespace: &str) {
+        let deadline = {
+            let mut apc = self.0.lock().unwrap();
+            let timeout = match apc.0.lazy_load {
+                ApolloLazyLoad::Disabled => return,
+                ApolloLazyLoad::Background => None,
+                ApolloLazyLoad::Wait(timeout) => Some(timeout),
             };
-            return Some(item);
+            if apc.0.closed || apc.1.iter().any(|ele| ele.lock().unwrap().namespace == namespace) {
+                return;
+            }
+            match apc.0.lazy_pending.get(namespace) {
+                Some(Some(failed)) if failed.elapsed() < LAZY_RETRY_INTERVAL => return,
+                Some(None) => {},
+                _ => {
+                    debug!("lazy load namespace {}", namespace);
+                    if apc.0.runtime.is_none() {
+                        drop(apc);
+                        self.listen_offline(namespace, 0);
+                        return;
+                    }
+                    apc.0.lazy_pending.insert(namespace.to_string(), None);
+                    let apc_2 = self.clone();
+                    let ns = namespace.to_string();
+                    apc.0.runtime.as_ref().unwrap().spawn(async move {
+                        let err = apc_2.listen_namespace(&ns).await;
+                        let mut apc = apc_2.0.lock().unwrap();
+                        if err.is_some() {
+                            log::error!("lazy load namespace {} failed, error: {:?}", &ns, err.unwrap());
+                            apc.0.lazy_pending.insert(ns, Some(std::time::Instant::now()));
+                        } else {
+                            apc.0.lazy_pending.remove(&ns);
+                        }
+                        apc.0.lazy_done.notify_all();
+                    });
+                },
+            }
+            timeout.map(|timeout| std::time::Instant::now() + timeout)
+        };
+        if deadline.is_none() {
+            return;
+        }
+
+        let deadline = deadline.unwrap();
+        let mut apc = self.0.lock().unwrap();
+        let lazy_done = apc.0.lazy_done.clone();
+        while matches!(apc.0.lazy_pending.get(namespace), Some(None)) {
+            let now = std::time::Instant::now();
+            if now >= deadline {
+                log::warn!("lazy load namespace {} timeout", namespace);
+                return;
+            }
+            apc = lazy_done.wait_timeout(apc, deadline - now).unwrap().0;
         }
-        return None;
     }
 
-    pub fn get_config_from_namespace(&self, key: &str, namespace: &str) -> Option<ApolloConfigItem> {
-        let cache = &self.0.lock().unwrap().1;
-        for ele in cache {
+    /// 开启/关闭`get_config`和`get_config_from_namespace`的读取统计, 关闭时清空已有统计.
+    /// 用于清理namespace时找出没有被读取的配置, 其他读取方式(`snapshot`, `get_by_prefix`, config/figment适配器等)不计入
+    pub fn enable_access_tracking(&self, enable: bool) {
+        let mut apc = self.0.lock().unwrap();
+        if !enable {
+            apc.0.access = None;
+        } else if apc.0.access.is_none() {
+            apc.0.access = Some(HashMap::new());
+        }
+    }
+
+    /// 读取统计报告, 未开启统计时所有列表为空. 通过`get_config`读取过的key在所有namespace中都视为已使用
+    pub fn access_report(&self) -> ApolloAccessReport {
+        let apc = self.0.lock().unwrap();
+        let empty = HashMap::new();
+        let access = apc.0.access.as_ref().unwrap_or(&empty);
+
+        let mut accessed: Vec<ApolloKeyAccess> = access.values().cloned().collect();
+        accessed.sort_by(|a, b| (&a.namespace, &a.key).cmp(&(&b.namespace, &b.key)));
+        let missing = accessed.iter().filter(|a| a.misses > 0).cloned().collect();
+
+        let mut unused = Vec::new();
+        if apc.0.access.is_some() {
+            for ele in &apc.1 {
+                let an = ele.lock().unwrap();
+                for key in an.configurations.keys() {
+                    let used = access.contains_key(&(None, key.clone())) || access.contains_key(&(Some(an.namespace.clone()), key.clone()));
+                    if !used {
+                        unused.push((an.namespace.clone(), key.clone()));
+                    }
+                }
+            }
+        }
+        unused.sort();
+        ApolloAccessReport { accessed, unused, missing }
+    }
+
+    /// 开启/关闭占位符解析, 开启后配置值中的 `${key}` / `${key:default}` 会按namespace优先级替换为对应配置,
+    /// 找不到时依次使用环境变量和默认值. 被引用的配置变更时, 引用方也会产生变更事件
+    pub fn enable_placeholder(&self, enable: bool) {
+        let mut apc = self.0.lock().unwrap();
+        if apc.0.placeholder == enable {
+            return;
+        }
+        let view_old = apollo_resolved_view(&apc.0, &apc.1);
+        apc.0.placeholder = enable;
+        let events = apollo_effective_diff(&apollo_resolved_view(&apc.0, &apc.1), &view_old);
+        apollo_emit_events(&apc.0, events);
+    }
+
+    /// all keys can be resolved by `get_config`, sorted
+    pub fn keys(&self) -> Vec<String> {
+        let apc = self.0.lock().unwrap();
+        let mut keys: Vec<String> = apollo_effective_view(&apc.0, &apc.1).into_keys().collect();
+        keys.sort();
+        keys
+    }
+
+    /// all keys of namespace, sorted. return None if namespace is not listened
+    pub fn keys_in_namespace(&self, namespace: &str) -> Option<Vec<String>> {
+        let apc = self.0.lock().unwrap();
+        for ele in &apc.1 {
             let an = ele.lock().unwrap();
             if an.namespace == namespace {
-                if an.configurations.contains_key(key) {
-                    let value = an.configurations.get(key);
-                    let item = ApolloConfigItem {
-                        config_key: key.to_string(),
-                        config_value: value.unwrap().to_string(),
-                        namespace: an.namespace.clone(),
-                    };
-                    return Some(item);
-                }
+                let mut keys: Vec<String> = an.configurations.keys().cloned().collect();
+                keys.sort();
+                return Some(keys);
             }
         }
         return None;
     }
 
+    /// 获取所有以prefix开头的配置, 按namespace优先级取值
+    pub fn get_by_prefix(&self, prefix: &str) -> HashMap<String, ApolloConfigItem> {
+        let mut res = self.snapshot();
+        res.retain(|key, _| key.starts_with(prefix));
+        res
+    }
+
+    /// 当前所有生效配置, 即对每个key调用`get_config`的结果
+    pub fn snapshot(&self) -> HashMap<String, ApolloConfigItem> {
+        let apc = self.0.lock().unwrap();
+        apollo_resolved_view(&apc.0, &apc.1)
+    }
+
+    /// 开启环境变量覆盖, key `db.host` 对应环境变量 `{prefix}DB_HOST`, 优先级高于本地覆盖文件和Apollo配置.
+    /// 仅覆盖本地覆盖文件或Apollo中存在的key, 传入None关闭
+    pub fn set_env_override(&self, prefix: Option<&str>) {
+        let mut apc = self.0.lock().unwrap();
+        let view_old = apollo_resolved_view(&apc.0, &apc.1);
+        apc.0.env_prefix = prefix.map(|p| p.to_string());
+        let events = apollo_effective_diff(&apollo_resolved_view(&apc.0, &apc.1), &view_old);
+        apollo_emit_events(&apc.0, events);
+    }
+
+    /// 设置本地覆盖配置文件(properties格式), 优先级高于Apollo配置, 文件修改后自动重新加载. 传入None移除
+    pub fn set_override_file(&self, path: Option<&str>) -> Option<ApolloError> {
+        let mut override_file = None;
+        if path.is_some() {
+            let res = OverrideFile::load(path.unwrap());
+            if res.is_err() {
+                return Some(res.unwrap_err());
+            }
+            override_file = Some(res.unwrap());
+        }
+        let mut apc = self.0.lock().unwrap();
+        let view_old = apollo_resolved_view(&apc.0, &apc.1);
+        apc.0.override_file = override_file;
+        let events = apollo_effective_diff(&apollo_resolved_view(&apc.0, &apc.1), &view_old);
+        apollo_emit_events(&apc.0, events);
+        None
+    }
+
+    /// 本地覆盖文件有修改时重新加载
+    fn reload_override_file(&self) {
+        let mut apc = self.0.lock().unwrap();
+        if apc.0.override_file.is_none() {
+            return;
+        }
+        let view_old = apollo_resolved_view(&apc.0, &apc.1);
+        let changed = apc.0.override_file.as_mut().unwrap().reload_if_modified();
+        if !changed {
+            return;
+        }
+        let events = apollo_effective_diff(&apollo_resolved_view(&apc.0, &apc.1), &view_old);
+        debug!("override file reloaded, change events: {:?}", events);
+        apollo_emit_events(&apc.0, events);
+    }
+
     /// pull config from namespace, and will listen change`s notify of this namespace, if namespace has be listened already, do nothing
-    /// 如果先后监听了多个namespace，排在后面的配置优先级更高
+    /// 以默认优先级0监听, 如果先后监听了多个namespace，排在后面的配置优先级更高
     pub async fn listen_namespace(&self, namespace: &str) -> Option<ApolloError> {
-        let load_res = self.load_namespace(namespace, false, None).await;
-        if load_res.is_err() {
-            let err = load_res.unwrap_err();
-            return Some(err);
+        self.listen_namespace_with_priority(namespace, 0).await
+    }
+
+    /// same as `listen_namespace`, config of namespace with greater priority will be taken first in `get_config`
+    /// 如果namespace已被监听, 不会修改其优先级, 请使用`set_namespace_priority`
+    pub async fn listen_namespace_with_priority(&self, namespace: &str, priority: i32) -> Option<ApolloError> {
+        if self.0.lock().unwrap().0.closed {
+            return Some(ApolloError::Closed);
         }
-        let cfg = load_res.unwrap();
-        if cfg.is_none() {
+        if self.0.lock().unwrap().0.offline {
+            self.listen_offline(namespace, priority);
             return None;
         }
-        let cfg = cfg.unwrap();
+        let load_res = self.load_namespace(namespace, false, None).await;
+        let cfg = match load_res {
+            Ok(None) => return None,
+            Ok(Some(cfg)) => cfg,
+            Err(ApolloError::NamespaceNotFound { .. }) if self.0.lock().unwrap().0.tolerate_not_found => {
+                log::warn!("namespace {} not found, listen it as an empty namespace", namespace);
+                let apc = self.0.lock().unwrap();
+                apollo_empty_namespace(&apc.0, namespace, format!("namespace {} not found", namespace), ApolloNamespaceSource::NotFound)
+            }
+            Err(err) => return Some(err),
+        };
         let mut apc = self.0.lock().unwrap();
         let cache = apc.1.clone();
 
         for ele in cache {
             let an = ele.lock().unwrap();
             if an.namespace == namespace {
-                let err_msg = format!("concurrent load namespace {} ", namespace);
-                return Some(ApolloError::new(255555, err_msg));
+                return Some(ApolloError::ConcurrentListen { namespace: namespace.to_string() });
             }
         }
-        apc.1.push(Arc::new(Mutex::new(cfg)));
+        let cfg = apollo_namespace{
+            priority,
+            notification_id: -1,
+            last_fetch: Some(SystemTime::now()),
+            ..cfg
+        };
+        apollo_cache_insert(&mut apc.1, cfg);
+        //新的namespace立即加入长轮询
+        apc.0.poll_restart.notify_one();
 
         None
     }
 
-    async fn load_namespace(&self, namespace: &str, force: bool, release_key: Option<String>) -> Result<Option<apollo_namespace>, ApolloError> {
-        let apc = self.0.lock().unwrap();
-        let cache = apc.1.clone();
-        if !force {
-            for ele in cache {
-                let an = ele.lock().unwrap();
-                if an.namespace == namespace {
-                    return Ok(None);
-                }
-            }
+    /// 不连接服务端时作为未发布的空namespace监听, 之后通过`apply_release`写入配置
+    fn listen_offline(&self, namespace: &str, priority: i32) {
+        let mut apc = self.0.lock().unwrap();
+        if apc.1.iter().any(|ele| ele.lock().unwrap().namespace == namespace) {
+            return;
         }
-        
-        let cfg_srv_list = &apc.0.config_srv_list;
+        let an = apollo_namespace {
+            priority,
+            ..apollo_empty_namespace(&apc.0, namespace, format!("namespace {} has not been published", namespace), ApolloNamespaceSource::NotFound)
+        };
+        apollo_cache_insert(&mut apc.1, an);
+    }
 
-        let mut res_err: Option<ApolloError> = None;
-        let mut rk = "".to_string();
-        if !force && release_key.is_some() {
-            rk = release_key.unwrap();
-        }
+    /// 开启后监听不存在的namespace(404)不会报错, 而是作为空namespace监听, namespace创建并发布后自动拉取配置.
+    /// `new`时传入的namespace在此之前已加载, 不受影响
+    pub fn tolerate_missing_namespace(&self, tolerate: bool) {
+        self.0.lock().unwrap().0.tolerate_not_found = tolerate;
+    }
 
-        let cli = reqwest::Client::new();
+    /// change priority of a listened namespace, return false if namespace is not listened
+    /// 生效值因此发生变化的配置会产生变更事件
+    pub fn set_namespace_priority(&self, namespace: &str, priority: i32) -> bool {
+        let mut apc = self.0.lock().unwrap();
+        let idx = apc.1.iter().position(|ele| ele.lock().unwrap().namespace == namespace);
+        if idx.is_none() {
+            return false;
+        }
+        let view_old = apollo_resolved_view(&apc.0, &apc.1);
+        let ele = apc.1.remove(idx.unwrap());
+        ele.lock().unwrap().priority = priority;
+        let idx = apollo_cache_position(&apc.1, priority);
+        apc.1.insert(idx, ele);
 
-        for cfg_srv_addr in cfg_srv_list {
-            let path = format!("/configs/{appId}/{clusterName}/{namespace}?releaseKey={releaseKey}", appId = &apc.0.app_id_default, clusterName = &apc.0.cluster_default, namespace = namespace, releaseKey=rk);
-            let mut req_builder = cli.get(format!("{config_server_url}{path}", config_server_url=cfg_srv_addr, path=path));
+        let events = apollo_effective_diff(&apollo_resolved_view(&apc.0, &apc.1), &view_old);
+        debug!("namespace {} priority changed to {}, change events: {:?}", namespace, priority, events);
+        apollo_emit_events(&apc.0, events);
+        true
+    }
 
-            let headers = apollo_req_sign(&apc.0.secret, &apc.0.app_id_default, &path);
-            for ele in headers {
-                req_builder = req_builder.header(ele.0, ele.1);
-            }
+    /// namespaces with their priority, in the order `get_config` resolves a key (highest priority first)
+    pub fn namespace_order(&self) -> Vec<(String, i32)> {
+        let apc = self.0.lock().unwrap();
+        apc.1.iter().rev().map(|ele| {
+            let an = ele.lock().unwrap();
+            (an.namespace.clone(), an.priority)
+        }).collect()
+    }
 
-            let response = req_builder.send().await;
-            if response.is_err() {
-                let err = response.unwrap_err();
-                log::error!("apollo config request execute failed, error:{:?}", &err);
-                res_err = Some(ApolloError::new(211111, err.to_string()));
-                continue;
-            }
-            let response = response.unwrap();
-            
-            if response.status() == 304 {
-                return Ok(None);
-            }
+    /// stop listening namespace and remove its config from cache, return false if namespace is not listened
+    /// 移除后无法再取到的配置会产生DELETE事件, 改由其他namespace提供的配置会产生UPDATE事件
+    pub fn unlisten_namespace(&self, namespace: &str) -> bool {
+        let mut apc = self.0.lock().unwrap();
+        let idx = apc.1.iter().position(|ele| ele.lock().unwrap().namespace == namespace);
+        if idx.is_none() {
+            return false;
+        }
+        let view_old = apollo_resolved_view(&apc.0, &apc.1);
+        let namespaces_old = apollo_namespace_views(&apc.0, &apc.1);
+        apc.1.remove(idx.unwrap());
+        apollo_emit_namespace_events(&apc.0, &apc.1, namespaces_old);
 
-            if response.status() != 200 {
-                let err = response.error_for_status().unwrap_err();
-                log::error!("read config failed! error: {:?}", &err);
-                res_err = Some(ApolloError::new(222222, err.to_string()));
-                continue;
-            }
+        let events = apollo_effective_diff(&apollo_resolved_view(&apc.0, &apc.1), &view_old);
+        debug!("unlisten namespace {}, change events: {:?}", namespace, events);
 
-            let cfg_resp = response.text().await;
-            if cfg_resp.is_err() {
-                let err = cfg_resp.unwrap_err();
-                log::error!("can not read config string from response, error:{:?}", err);
-                res_err = Some(ApolloError::new(23333, err.to_string()));
-                continue;
-            }
-            let cfg_str = cfg_resp.unwrap();
+        apollo_emit_events(&apc.0, events);
+        true
+    }
 
-            let cfg_de: Result<apollo_namespace, serde_json::Error> = serde_json::from_str(&cfg_str);
-            if cfg_de.is_err() {
-                let err = cfg_de.unwrap_err();
-                log::error!("deserialize config failed! response: {}, error: {:?}", cfg_str, err);
-                return Err(ApolloError::new(24444, err.to_string())); //deserialize error, do not retry
+    async fn load_namespace(&self, namespace: &str, force: bool, release_key: Option<String>) -> Result<Option<apollo_namespace>, ApolloError> {
+        let mut messages = None;
+        let (cfg_srv_list, app_id, cluster, signer) = {
+            let apc = self.0.lock().unwrap();
+            for ele in &apc.1 {
+                let an = ele.lock().unwrap();
+                if an.namespace == namespace {
+                    if !force {
+                        return Ok(None);
+                    }
+                    messages = an.messages.clone();
+                }
             }
-            let cfg = cfg_de.unwrap();
+            (apc.0.config_srv_list.clone(), apc.0.app_id_default.clone(), apc.0.cluster_default.clone(), apc.0.signer.clone())
+        };
 
-            return Ok(Some(cfg));
+        let mut rk = "".to_string();
+        if release_key.is_some() {
+            rk = release_key.unwrap();
         }
-        if res_err.is_none() {
-            Ok(None)
-        }else {
-            Err(res_err.unwrap())
+        let rk: String = url::form_urlencoded::byte_serialize(rk.as_bytes()).collect();
+        let mut messages_query = "".to_string();
+        if messages.is_some() {
+            let messages_str = serde_json::to_string(&messages.unwrap()).unwrap();
+            let messages_str: String = url::form_urlencoded::byte_serialize(messages_str.as_bytes()).collect();
+            messages_query = format!("&messages={}", messages_str);
         }
+
+        let start = std::time::Instant::now();
+        let res = apollo_fetch_namespace(cfg_srv_list, &app_id, &cluster, &signer, namespace, &rk, &messages_query).await;
+        let outcome = match &res {
+            Ok(Some(_)) => "updated",
+            Ok(None) => "not_modified",
+            Err(err) => metrics::error_outcome(err),
+        };
+        metrics::timed("apollo_config_fetch_total", "apollo_config_fetch_duration_seconds", &[("namespace", namespace), ("outcome", outcome)], start.elapsed());
+        trace_event!(tracing::Level::DEBUG, app_id = %app_id, cluster = %cluster, namespace, outcome, elapsed_ms = start.elapsed().as_millis() as u64, "apollo config fetched");
+        res
     }
 
     pub fn close(&self) {
         let mut apc = self.0.lock().unwrap();
+        apc.0.closed = true;
         let res = apc.2.send(false);
         if res.is_err() {
             log::warn!("apollo client has closed...");
             return;
         }
         apc.1.clear();
-        //todo 加个close标志位？
+    }
+
+    /// 设置access key secret, 轮换期间可配置多个, 签名校验失败(401)时依次尝试. 会覆盖`new`时传入的secret
+    pub fn set_access_keys(&self, source: AccessKeySource) {
+        self.0.lock().unwrap().0.signer.set_access_keys(source);
+    }
+
+    /// 替换默认的HMAC-SHA1签名实现
+    pub fn set_request_signer(&self, signer: Arc<dyn RequestSigner>) {
+        self.0.lock().unwrap().0.signer.set_signer(signer);
+    }
+
+    /// client 和各namespace的运行状态
+    pub fn status(&self) -> ApolloClientStatus {
+        let apc = self.0.lock().unwrap();
+        let namespaces = apc.1.iter().rev().map(|ele| {
+            let an = ele.lock().unwrap();
+            ApolloNamespaceStatus {
+                namespace: an.namespace.clone(),
+                priority: an.priority,
+                release_key: an.release_key.clone(),
+                notification_id: an.notification_id,
+                last_fetch: an.last_fetch,
+                last_error: an.last_error.clone(),
+                source: an.source.clone(),
+                rejected_release_key: an.rejected_release_key.clone(),
+                frozen_release_key: an.frozen_release_key.clone(),
+            }
+        }).collect();
+        ApolloClientStatus {
+            app_id: apc.0.app_id_default.clone(),
+            cluster: apc.0.cluster_default.clone(),
+            config_servers: apc.0.config_srv_list.clone(),
+            last_long_poll: apc.0.last_long_poll,
+            last_long_poll_error: apc.0.last_long_poll_error.clone(),
+            closed: apc.0.closed,
+            namespaces,
+        }
+    }
+
+    /// 用新发布的配置替换namespace的配置, namespace未被监听时以优先级0监听, 产生变更事件
+    #[cfg(any(test, feature = "testing"))]
+    pub(crate) fn apply_release(&self, namespace: &str, release_key: &str, configurations: HashMap<String, String>) {
+        if self.skip_frozen(namespace, release_key, None) {
+            return;
+        }
+        if !self.check_release(namespace, release_key, &configurations, None) {
+            return;
+        }
+        let mut apc = self.0.lock().unwrap();
+        if apc.0.closed {
+            return;
+        }
+        let view_old = apollo_resolved_view(&apc.0, &apc.1);
+        let namespaces_old = apollo_namespace_views(&apc.0, &apc.1);
+        let ele = apc.1.iter().find(|ele| ele.lock().unwrap().namespace == namespace).cloned();
+        if ele.is_some() {
+            let ele = ele.unwrap();
+            let mut an = ele.lock().unwrap();
+            an.release_key = release_key.to_string();
+            an.configurations = configurations;
+            an.last_fetch = Some(SystemTime::now());
+            an.last_error = None;
+            an.source = ApolloNamespaceSource::Remote;
+            an.rejected_release_key = None;
+        } else {
+            let an = apollo_namespace {
+                app_id: apc.0.app_id_default.clone(),
+                cluster: apc.0.cluster_default.clone(),
+                namespace: namespace.to_string(),
+                release_key: release_key.to_string(),
+                configurations,
+                notification_id: -1,
+                priority: 0,
+                messages: None,
+                last_fetch: Some(SystemTime::now()),
+                last_error: None,
+                source: ApolloNamespaceSource::Remote,
+                rejected_release_key: None,
+                frozen_release_key: None,
+            };
+            apollo_cache_insert(&mut apc.1, an);
+        }
+
+        apollo_emit_namespace_events(&apc.0, &apc.1, namespaces_old);
+        let events = apollo_effective_diff(&apollo_resolved_view(&apc.0, &apc.1), &view_old);
+        apollo_emit_events(&apc.0, events);
+    }
+
+    /// 订阅变更事件, 每个receiver独立接收订阅之后的所有事件, 不影响`fetch_change_event`
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<Vec<ApolloChangeEvent>> {
+        self.0.lock().unwrap().0.change_event_tx.subscribe()
+    }
+
+    /// 订阅各namespace自身配置的变更, 不考虑namespace之间的优先级, 被更高优先级namespace遮盖的key变更时也会通知.
+    /// 事件的namespace为发生变化的namespace, 值为未解析占位符的原始值, 不包括环境变量和本地覆盖文件.
+    /// 按namespace读取配置(`get_config_from_namespace`)时用于判断是否需要重新读取
+    pub fn subscribe_namespaces(&self) -> tokio::sync::broadcast::Receiver<Vec<ApolloChangeEvent>> {
+        self.0.lock().unwrap().0.namespace_event_tx.subscribe()
+    }
+
+    /// 订阅被validator拒绝的发布, 见`add_release_validator`. 拒绝不改变配置, 不产生变更事件
+    pub fn subscribe_rejections(&self) -> tokio::sync::broadcast::Receiver<ApolloReleaseRejection> {
+        self.0.lock().unwrap().0.rejection_tx.subscribe()
+    }
+
+    /// 同`subscribe`, 在window内连续发布产生的多批事件合并为一批净变化, 见`source::debounce`.
+    /// 在监听线程的runtime中转发, client关闭或返回的receiver drop后退出
+    pub fn subscribe_debounced(&self, window: std::time::Duration) -> tokio::sync::broadcast::Receiver<Vec<ApolloChangeEvent>> {
+        let apc = self.0.lock().unwrap();
+        let rx = apc.0.change_event_tx.subscribe();
+        match apc.0.runtime.as_ref() {
+            Some(runtime) => super::source::debounce_on(runtime, rx, window),
+            None => super::source::debounce(rx, window),
+        }
+    }
+
+    /// 等待所有key都能通过`get_config`取到值, 随变更事件重新检查. 超时返回`ApolloError::Timeout`,
+    /// 等待期间client被关闭返回`ApolloError::Closed`. 不计入访问统计
+    pub async fn wait_for(&self, keys: &[&str], timeout: std::time::Duration) -> Result<(), ApolloError> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let (mut rx, mut close_rx) = {
+            let apc = self.0.lock().unwrap();
+            (apc.0.change_event_tx.subscribe(), apc.2.subscribe())
+        };
+        loop {
+            {
+                let apc = self.0.lock().unwrap();
+                if apc.0.closed {
+                    return Err(ApolloError::Closed);
+                }
+                if keys.iter().all(|key| apollo_lookup(&apc.0, &apc.1, key).is_some()) {
+                    return Ok(());
+                }
+            }
+            tokio::select! {
+                _ = tokio::time::sleep_until(deadline) => return Err(ApolloError::Timeout),
+                _ = close_rx.changed() => {},
+                res = rx.recv() => {
+                    if let Err(tokio::sync::broadcast::error::RecvError::Closed) = res {
+                        return Err(ApolloError::Closed);
+                    }
+                },
+            }
+        }
     }
 
     /// try fetch change event, non block
@@ -310,27 +1104,42 @@ impl ApolloConfigClient {
         Some(res)
     }
 
+    /// 等待delay后发起长轮询
+    async fn namespace_notify_after(&self, delay: std::time::Duration) -> Vec<notification_item> {
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+        self.namespace_notify().await
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "apollo.notification_poll", skip_all, fields(app_id = tracing::field::Empty, cluster = tracing::field::Empty, server = tracing::field::Empty, namespaces = tracing::field::Empty)))]
     async fn namespace_notify(&self) -> Vec<notification_item> {
         let mut ns_list = Vec::new();
-        let apc = self.0.lock().unwrap();
-        for ele in &apc.1 {
-            let an = ele.lock().unwrap();
-            let ni = notification_item{
-                namespace: an.namespace.clone(),
-                notification_id: an.notification_id,
-            };
-            ns_list.push(ni);
-        }
+        let (cfg_srv_addr, app_id, cluster, signer) = {
+            let apc = self.0.lock().unwrap();
+            for ele in &apc.1 {
+                let an = ele.lock().unwrap();
+                let ni = notification_item{
+                    namespace: an.namespace.clone(),
+                    notification_id: an.notification_id,
+                    messages: an.messages.clone(),
+                };
+                ns_list.push(ni);
+            }
+            (apc.0.config_srv_list.first().cloned(), apc.0.app_id_default.clone(), apc.0.cluster_default.clone(), apc.0.signer.clone())
+        };
 
-        let cfg_srv_addr = apc.0.config_srv_list.get(0);
         if cfg_srv_addr.is_none() {
             log::warn!("no valid config server address...ensure server is working.....");
             return Vec::new();
         }
-        let cfg_srv_addr = cfg_srv_addr.unwrap().clone();
+        let cfg_srv_addr = cfg_srv_addr.unwrap();
+        record_field!("app_id", &app_id);
+        record_field!("cluster", &cluster);
+        record_field!("server", &cfg_srv_addr);
+        record_field!("namespaces", ns_list.len());
         
         if ns_list.len() == 0 {
-            drop(apc);
             tokio::time::sleep(std::time::Duration::from_secs(3)).await;
             return ns_list;
         }
@@ -338,49 +1147,104 @@ impl ApolloConfigClient {
         let notify_str = serde_json::to_string(&ns_list).unwrap();
         let notify_str: String = url::form_urlencoded::byte_serialize(notify_str.as_bytes()).collect();
 
-        let notify_url_path = format!("/notifications/v2?appId={}&cluster={}&notifications={}", apc.0.app_id_default, apc.0.cluster_default, notify_str);
+        let notify_url_path = format!("/notifications/v2?appId={}&cluster={}&notifications={}", app_id, cluster, notify_str);
         
         let cli = reqwest::Client::new();
-        let mut req_builder = cli.get(format!("{host}{path}", host=cfg_srv_addr, path=notify_url_path));
-        let headers = apollo_req_sign(&apc.0.secret, &apc.0.app_id_default, &notify_url_path);
-        for ele in headers {
-            req_builder = req_builder.header(ele.0, ele.1);
-        }
-        drop(apc);
+        let url = format!("{host}{path}", host=cfg_srv_addr, path=notify_url_path);
 
-        let resp = req_builder.send().await;
+        let start = std::time::Instant::now();
+        let resp = signer.get(&cli, &url, &app_id).await;
         if resp.is_err() {
             let err = resp.unwrap_err();
             log::warn!("apollo notification failed, error: {:?}", err);
+            self.long_poll_failed(start, err.to_string());
             tokio::time::sleep(std::time::Duration::from_secs(1)).await;
             return Vec::new();
         }
         
         let resp = resp.unwrap();
+        //长轮询超时, 没有namespace变化
+        if resp.status() == 304 {
+            self.long_poll_succeeded(start, "not_modified");
+            return Vec::new();
+        }
+        let status = resp.status();
         let cont = resp.text().await;
         if cont.is_err() {
-            log::warn!("read notification result failed! error: {:?}", cont.unwrap_err());
+            let err = cont.unwrap_err();
+            log::warn!("read notification result failed! error: {:?}", err);
+            self.long_poll_failed(start, err.to_string());
             tokio::time::sleep(std::time::Duration::from_secs(1)).await;
             return Vec::new();
         }
         let cont = cont.unwrap();
-        if cont.len() < 10 {
+        if status != 200 {
+            log::warn!("apollo notification failed, status: {}, response: {}", status, cont);
+            self.long_poll_failed(start, format!("status {}: {}", status, cont));
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
             return Vec::new();
         }
 
         let ns_changed: Result<Vec<notification_item>, serde_json::Error> = serde_json::from_str(&cont);
         if ns_changed.is_err() {
-            log::warn!("can not deserialize notification response! response str: {}, error:{:?}", cont, ns_changed.unwrap_err());
+            let err = ns_changed.unwrap_err();
+            log::warn!("can not deserialize notification response! response str: {}, error:{:?}", cont, err);
+            self.long_poll_failed(start, err.to_string());
             return Vec::new();
         }
+        self.long_poll_succeeded(start, "changed");
         let ns_changed = ns_changed.unwrap();
         ns_changed
     }
 
-    async fn loop_listening(&self, mut close_rx: tokio::sync::watch::Receiver<bool>, change_event_tx: tokio::sync::broadcast::Sender<Vec<ApolloChangeEvent>>) {
+    fn long_poll_succeeded(&self, start: std::time::Instant, outcome: &str) {
+        trace_event!(tracing::Level::DEBUG, outcome, elapsed_ms = start.elapsed().as_millis() as u64, "apollo notification poll finished");
+        metrics::timed("apollo_long_poll_total", "apollo_long_poll_duration_seconds", &[("outcome", outcome)], start.elapsed());
+        let mut apc = self.0.lock().unwrap();
+        apc.0.last_long_poll = Some(SystemTime::now());
+        apc.0.last_long_poll_error = None;
+    }
+
+    fn long_poll_failed(&self, start: std::time::Instant, err: String) {
+        trace_event!(tracing::Level::WARN, error = %err, elapsed_ms = start.elapsed().as_millis() as u64, "apollo notification poll failed");
+        metrics::timed("apollo_long_poll_total", "apollo_long_poll_duration_seconds", &[("outcome", "error")], start.elapsed());
+        self.0.lock().unwrap().0.last_long_poll_error = Some(err);
+    }
+
+    /// 各namespace距最近一次成功拉取的时间
+    fn record_cache_age(&self) {
+        let now = SystemTime::now();
+        for ele in &self.0.lock().unwrap().1 {
+            let an = ele.lock().unwrap();
+            if an.last_fetch.is_none() {
+                continue;
+            }
+            let age = now.duration_since(an.last_fetch.unwrap()).unwrap_or_default();
+            metrics::gauge("apollo_namespace_cache_age_seconds", &[("namespace", &an.namespace)], age.as_secs_f64());
+        }
+    }
+
+    fn update_namespace(&self, namespace: &str, f: impl FnOnce(&mut apollo_namespace)) {
+        for ele in &self.0.lock().unwrap().1 {
+            let mut an = ele.lock().unwrap();
+            if an.namespace == namespace {
+                f(&mut an);
+                return;
+            }
+        }
+    }
+
+    async fn loop_listening(&self, mut close_rx: tokio::sync::watch::Receiver<bool>) {
         let start = tokio::time::Instant::now().checked_add(tokio::time::Duration::from_secs(5)).unwrap();
         let mut meta_refresh_ticker = tokio::time::interval_at(start, std::time::Duration::from_secs(30));
-        
+        let mut override_file_ticker = tokio::time::interval(std::time::Duration::from_secs(3));
+        //长轮询请求不随其他分支打断, 完成后再发起下一次
+        let notify = self.namespace_notify_after(std::time::Duration::ZERO);
+        tokio::pin!(notify);
+        //重新拉取配置失败(可重试的错误)后, 推迟下一次长轮询, 避免服务端立即返回同一通知时持续重试
+        let mut reload_backoff = std::time::Duration::ZERO;
+        let poll_restart = self.0.lock().unwrap().0.poll_restart.clone();
+
         loop {
             tokio::select! {
                 //监听关闭
@@ -391,8 +1255,9 @@ impl ApolloConfigClient {
                 }
                 //刷新config srv
                 _ = meta_refresh_ticker.tick() => {
-                    let mut apc = self.0.lock().unwrap();
-                    let cfg_srv_res = apc.0.meta_server.get_config_servers(ApolloServerEnum::ConfigServer).await;
+                    self.record_cache_age();
+                    let meta_server = self.0.lock().unwrap().0.meta_server.clone();
+                    let cfg_srv_res = meta_server.get_config_servers(ApolloServerEnum::ConfigServer).await;
 
                     if cfg_srv_res.is_err() {
                         log::warn!("get apollo config server addr failed! {:?}", cfg_srv_res.unwrap_err());
@@ -402,23 +1267,34 @@ impl ApolloConfigClient {
                             log::warn!("apollo config server addr list is empty, check servers status...");
                         }else {
                             debug!("apollo config  server address:{:?}", config_srvs);
-                            apc.0.config_srv_list = config_srvs;
+                            self.0.lock().unwrap().0.config_srv_list = config_srvs;
                         }
                     }
                 }
+                //监听本地覆盖文件
+                _ = override_file_ticker.tick() => {
+                    self.reload_override_file();
+                }
+                //监听或解除冻结namespace后重新发起长轮询
+                _ = poll_restart.notified() => {
+                    notify.set(self.namespace_notify_after(reload_backoff));
+                }
                 //监听配置变更
-                v = self.namespace_notify() => {
-                    let apc = self.0.lock().unwrap();
-                    let cache = &apc.1;
+                v = &mut notify => {
+                    let mut reload_failed = false;
                     let mut change_ns = HashMap::new();
                     let mut release_key_map = HashMap::new();
 
-                    for ele in cache {
-                        let an = ele.lock().unwrap();
+                    for ele in &self.0.lock().unwrap().1 {
+                        let mut an = ele.lock().unwrap();
                         release_key_map.insert(an.namespace.clone(), an.release_key.clone());
+                        //先合并messages, 拉取配置时带上
+                        let ni = v.iter().find(|ni| ni.namespace == an.namespace);
+                        if ni.is_some() && ni.unwrap().messages.is_some() {
+                            let messages = ni.unwrap().messages.as_ref().unwrap();
+                            an.messages.get_or_insert_with(Default::default).merge(messages);
+                        }
                     }
-                    drop(cache);
-                    drop(apc);
 
                     for ele in v {
                         let release_key = release_key_map.remove(&ele.namespace);
@@ -430,16 +1306,37 @@ impl ApolloConfigClient {
                         if cfg_res.is_err() {
                             let err = cfg_res.unwrap_err();
                             log::error!("reload config for namespace {} failed, notifyId: {}, releaseKey:{:?}, error:{:?}", &ele.namespace, ele.notification_id, &release_key, err);
+                            let retryable = err.is_retryable();
+                            self.update_namespace(&ele.namespace, |an| {
+                                an.last_error = Some(err.to_string());
+                                //namespace不存在, 签名错误等重试也不会成功, 等下一次通知再拉取
+                                if !retryable {
+                                    an.notification_id = ele.notification_id;
+                                }
+                            });
+                            reload_failed = reload_failed || retryable;
                             continue;
                         }
                         let cfg = cfg_res.unwrap();
                         if cfg.is_none() {
                             log::debug!("config no changed. namespace:{}, releaseKey:{:?}, notifyId:{}", &ele.namespace, &release_key, ele.notification_id);
+                            self.update_namespace(&ele.namespace, |an| {
+                                an.notification_id = ele.notification_id;
+                                an.last_fetch = Some(SystemTime::now());
+                                an.last_error = None;
+                            });
                             continue;
                         }
                         let cfg = cfg.unwrap();
+                        if self.skip_frozen(&ele.namespace, &cfg.release_key, Some(ele.notification_id)) {
+                            continue;
+                        }
+                        if !self.check_release(&ele.namespace, &cfg.release_key, &cfg.configurations, Some(ele.notification_id)) {
+                            continue;
+                        }
                         let cfg_new = apollo_namespace{
                             notification_id: ele.notification_id,
+                            last_fetch: Some(SystemTime::now()),
                             ..cfg
                         };
                         change_ns.insert(ele.namespace, cfg_new);
@@ -449,59 +1346,333 @@ impl ApolloConfigClient {
                     if change_ns.len() > 0 {
                         let mut apc = self.0.lock().unwrap();
                         let cache = &apc.1.clone();
+                        let view_old = apollo_resolved_view(&apc.0, cache);
+                        let namespaces_old = apollo_namespace_views(&apc.0, cache);
 
                         let mut cache_new = Vec::new();
                         for ele in cache {
                             let an = ele.lock().unwrap();
                             if change_ns.contains_key(&an.namespace) {
                                 let cfg = change_ns.remove(&an.namespace).unwrap();
-                                let diff = apollo_namespace_diff(&cfg, &an);
-                                if diff.len() > 0 {
-                                    let _ = change_event_tx.send(diff);     //check result?
-                                }
+                                let cfg = apollo_namespace{
+                                    priority: an.priority,
+                                    messages: an.messages.clone(),
+                                    frozen_release_key: an.frozen_release_key.clone(),
+                                    ..cfg
+                                };
                                 cache_new.push(Arc::new(Mutex::new(cfg)));
                             }else {
                                 cache_new.push(ele.clone());
                             }
                         }
                         apc.1 = cache_new;
+
+                        apollo_emit_namespace_events(&apc.0, &apc.1, namespaces_old);
+                        let diff = apollo_effective_diff(&apollo_resolved_view(&apc.0, &apc.1), &view_old);
+                        apollo_emit_events(&apc.0, diff);
                     }
 
+                    reload_backoff = match reload_failed {
+                        true => (reload_backoff * 2).clamp(std::time::Duration::from_secs(1), std::time::Duration::from_secs(60)),
+                        false => std::time::Duration::ZERO,
+                    };
+                    if reload_failed {
+                        log::warn!("reload config failed, next notification poll in {:?}", reload_backoff);
+                    }
+                    notify.set(self.namespace_notify_after(reload_backoff));
                 }       //config change listening
             }
         }
     }
 }
 
-/// request signature
-fn apollo_req_sign(secert: &str, app_id: &str, path: &str) -> Vec<(String, String)> {
-    let mut res = Vec::new();
-    if secert.len() == 0 {
-        return res;
+/// 依次从config server拉取namespace配置, 网络错误时换下一个地址重试
+#[cfg_attr(feature = "tracing", tracing::instrument(name = "apollo.config_fetch", skip_all, fields(app_id = app_id, cluster = cluster, namespace = namespace, release_key = rk, server = tracing::field::Empty)))]
+async fn apollo_fetch_namespace(cfg_srv_list: Vec<String>, app_id: &str, cluster: &str, signer: &ApolloSigner, namespace: &str, rk: &str, messages_query: &str) -> Result<Option<apollo_namespace>, ApolloError> {
+    let mut res_err: Option<ApolloError> = None;
+    let cli = reqwest::Client::new();
+
+    for cfg_srv_addr in cfg_srv_list {
+        let path = format!("/configs/{appId}/{clusterName}/{namespace}?releaseKey={releaseKey}{messages}", appId = app_id, clusterName = cluster, namespace = namespace, releaseKey=rk, messages=messages_query);
+        let url = format!("{config_server_url}{path}", config_server_url=cfg_srv_addr, path=path);
+        record_field!("server", &cfg_srv_addr);
+
+        let response = signer.get(&cli, &url, app_id).await;
+        if response.is_err() {
+            let err = response.unwrap_err();
+            log::error!("apollo config request execute failed, error:{:?}", &err);
+            res_err = Some(ApolloError::Transport(err));
+            continue;
+        }
+        let response = response.unwrap();
+        
+        if response.status() == 304 {
+            return Ok(None);
+        }
+
+        //namespace不存在或签名错误, 换其他服务地址重试也不会成功
+        if response.status() == 404 {
+            log::error!("namespace {} not found, appId: {}, cluster: {}", namespace, app_id, cluster);
+            return Err(ApolloError::NamespaceNotFound { namespace: namespace.to_string() });
+        }
+        if response.status() == 401 || response.status() == 403 {
+            let status = response.status().as_u16();
+            let message = response.text().await.unwrap_or_default();
+            log::error!("load namespace {} unauthorized, check access key secret. status: {}, response: {}", namespace, status, &message);
+            return Err(ApolloError::Unauthorized { status, message });
+        }
+
+        if response.status() != 200 {
+            let status = response.status().as_u16();
+            let message = response.text().await.unwrap_or_default();
+            log::error!("read config failed! status: {}, response: {}", status, &message);
+            res_err = Some(ApolloError::HttpStatus { status, message });
+            continue;
+        }
+
+        let cfg_resp = response.text().await;
+        if cfg_resp.is_err() {
+            let err = cfg_resp.unwrap_err();
+            log::error!("can not read config string from response, error:{:?}", err);
+            res_err = Some(ApolloError::Transport(err));
+            continue;
+        }
+        let cfg_str = cfg_resp.unwrap();
+
+        let cfg_de: Result<apollo_namespace, serde_json::Error> = serde_json::from_str(&cfg_str);
+        if cfg_de.is_err() {
+            let err = cfg_de.unwrap_err();
+            log::error!("deserialize config failed! response: {}, error: {:?}", cfg_str, err);
+            return Err(ApolloError::Deserialize(err)); //deserialize error, do not retry
+        }
+        let cfg = cfg_de.unwrap();
+
+        return Ok(Some(cfg));
     }
-    let ts = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis();
-    let raw_str = format!("{}\n{}", ts.to_string(), &path);
-    let mut mac = crypto::hmac::Hmac::new(crypto::sha1::Sha1::new(), secert.as_bytes());
-    mac.input(raw_str.as_bytes());
-    let binding = mac.result();
-    let token = binding.code();
-    let token = base64::encode(token);
+    if res_err.is_none() {
+        return Err(ApolloError::Discovery("no valid config server address".to_string()));
+    }
+    Err(res_err.unwrap())
+}
 
-    let sign = format!("Apollo {}:{}", app_id, token);
+/// 发送变更事件, 没有订阅者时忽略
+fn apollo_emit_events(cc: &apollo_config_client, events: Vec<ApolloChangeEvent>) {
+    if events.len() == 0 {
+        return;
+    }
+    for event in &events {
+        let action = match event.action {
+            ApolloChangeAction::ADD => "add",
+            ApolloChangeAction::UPDATE => "update",
+            ApolloChangeAction::DELETE => "delete",
+        };
+        metrics::counter("apollo_change_events_total", &[("namespace", &event.namespace), ("action", action)], 1);
+    }
+    let _ = cc.change_event_tx.send(events);
+}
 
-    res.push(("Timestamp".to_string(), ts.to_string()));
-    res.push(("Authorization".to_string(), sign));
-    return res;
+fn apollo_record_access(cc: &mut apollo_config_client, namespace: Option<&str>, key: &str, found: bool) {
+    if cc.access.is_none() {
+        return;
+    }
+    let access = cc.access.as_mut().unwrap();
+    let entry = access.entry((namespace.map(|ns| ns.to_string()), key.to_string())).or_insert_with(|| ApolloKeyAccess {
+        namespace: namespace.map(|ns| ns.to_string()),
+        key: key.to_string(),
+        hits: 0,
+        misses: 0,
+    });
+    if found {
+        entry.hits += 1;
+    } else {
+        entry.misses += 1;
+    }
+}
+
+/// 各namespace自身的配置, 没有`subscribe_namespaces`的订阅者时为None
+fn apollo_namespace_views(cc: &apollo_config_client, cache: &config_cache) -> Option<HashMap<String, HashMap<String, String>>> {
+    if cc.namespace_event_tx.receiver_count() == 0 {
+        return None;
+    }
+    let views = cache.iter().map(|ele| {
+        let an = ele.lock().unwrap();
+        (an.namespace.clone(), an.configurations.clone())
+    }).collect();
+    Some(views)
 }
 
-/// 比较新旧配置区别
-fn apollo_namespace_diff(new_cfg: &apollo_namespace, old_cfg: &apollo_namespace) -> Vec<ApolloChangeEvent> {
+/// 比较各namespace自身配置的区别, 通知`subscribe_namespaces`的订阅者
+fn apollo_emit_namespace_events(cc: &apollo_config_client, cache: &config_cache, views_old: Option<HashMap<String, HashMap<String, String>>>) {
+    if views_old.is_none() {
+        return;
+    }
+    let views_old = views_old.unwrap();
+    let views_new = apollo_namespace_views(cc, cache).unwrap_or_default();
+    let empty = HashMap::new();
+    let namespaces: std::collections::BTreeSet<&String> = views_old.keys().chain(views_new.keys()).collect();
+    let mut events = Vec::new();
+    for namespace in namespaces {
+        let old = views_old.get(namespace).unwrap_or(&empty);
+        let new = views_new.get(namespace).unwrap_or(&empty);
+        let event = |key: &String, value: &str, action| ApolloChangeEvent {
+            namespace: namespace.clone(),
+            key: key.clone(),
+            new_value: value.to_string(),
+            action,
+        };
+        for (key, value) in old {
+            match new.get(key) {
+                None => events.push(event(key, "", ApolloChangeAction::DELETE)),
+                Some(v) if v != value => events.push(event(key, v, ApolloChangeAction::UPDATE)),
+                _ => {}
+            }
+        }
+        for (key, value) in new {
+            if !old.contains_key(key) {
+                events.push(event(key, value, ApolloChangeAction::ADD));
+            }
+        }
+    }
+    if events.len() > 0 {
+        let _ = cc.namespace_event_tx.send(events);
+    }
+}
+
+/// 按优先级插入缓存, 优先级相同时排在后面
+fn apollo_cache_insert(cache: &mut config_cache, an: apollo_namespace) {
+    let idx = apollo_cache_position(cache, an.priority);
+    cache.insert(idx, Arc::new(Mutex::new(an)));
+}
+
+fn apollo_cache_position(cache: &config_cache, priority: i32) -> usize {
+    cache.iter().position(|ele| ele.lock().unwrap().priority > priority).unwrap_or(cache.len())
+}
+
+/// 按来源优先级查找配置, 不解析占位符
+fn apollo_lookup(cc: &apollo_config_client, cache: &config_cache, key: &str) -> Option<ApolloConfigItem> {
+    let mut item = None;
+    let mut idx = cache.len() as i32 - 1;
+    while idx >= 0 {
+        let an = cache[idx as usize].lock().unwrap();
+        idx -= 1;
+        let value = an.configurations.get(key);
+        if value.is_none() {
+            continue;
+        }
+        item = Some(ApolloConfigItem{
+            config_key: key.to_string(),
+            config_value: value.unwrap().to_string(),
+            namespace: an.namespace.clone(),
+            layer: ApolloConfigLayer::Apollo,
+        });
+        break;
+    }
+
+    if cc.override_file.is_some() {
+        let file = cc.override_file.as_ref().unwrap();
+        let value = file.configurations.get(key);
+        if value.is_some() {
+            item = Some(ApolloConfigItem{
+                config_key: key.to_string(),
+                config_value: value.unwrap().to_string(),
+                namespace: file.path.clone(),
+                layer: ApolloConfigLayer::OverrideFile,
+            });
+        }
+    }
+
+    if item.is_some() && cc.env_prefix.is_some() {
+        let env = env_override(cc.env_prefix.as_ref().unwrap(), key);
+        if env.is_some() {
+            let (name, value) = env.unwrap();
+            item = Some(ApolloConfigItem{
+                config_key: key.to_string(),
+                config_value: value,
+                namespace: name,
+                layer: ApolloConfigLayer::Env,
+            });
+        }
+    }
+    item
+}
+
+/// 所有来源合并后的生效配置
+fn apollo_effective_view(cc: &apollo_config_client, cache: &config_cache) -> HashMap<String, ApolloConfigItem> {
+    let mut view = HashMap::new();
+    for ele in cache {
+        let an = ele.lock().unwrap();
+        for (key, value) in &an.configurations {
+            let item = ApolloConfigItem{
+                config_key: key.clone(),
+                config_value: value.clone(),
+                namespace: an.namespace.clone(),
+                layer: ApolloConfigLayer::Apollo,
+            };
+            view.insert(key.clone(), item);
+        }
+    }
+
+    if cc.override_file.is_some() {
+        let file = cc.override_file.as_ref().unwrap();
+        for (key, value) in &file.configurations {
+            let item = ApolloConfigItem{
+                config_key: key.clone(),
+                config_value: value.clone(),
+                namespace: file.path.clone(),
+                layer: ApolloConfigLayer::OverrideFile,
+            };
+            view.insert(key.clone(), item);
+        }
+    }
+
+    if cc.env_prefix.is_some() {
+        let prefix = cc.env_prefix.as_ref().unwrap();
+        for (key, item) in view.iter_mut() {
+            let env = env_override(prefix, key);
+            if env.is_some() {
+                let (name, value) = env.unwrap();
+                item.config_value = value;
+                item.namespace = name;
+                item.layer = ApolloConfigLayer::Env;
+            }
+        }
+    }
+    view
+}
+
+/// 生效配置, 开启占位符解析时为解析后的值
+fn apollo_resolved_view(cc: &apollo_config_client, cache: &config_cache) -> HashMap<String, ApolloConfigItem> {
+    let mut view = apollo_effective_view(cc, cache);
+    if !cc.placeholder {
+        return view;
+    }
+    let raw: HashMap<String, String> = view.iter().map(|(k, v)| (k.clone(), v.config_value.clone())).collect();
+    let lookup = |key: &str| raw.get(key).cloned();
+    for (_, item) in view.iter_mut() {
+        if has_placeholder(&item.config_value) {
+            item.config_value = apollo_resolve_placeholder(&item.config_value, &lookup);
+        }
+    }
+    view
+}
+
+/// 解析单个配置值中的占位符, 只查找被引用的key
+fn apollo_resolve_value(cc: &apollo_config_client, cache: &config_cache, value: String) -> String {
+    if !cc.placeholder || !has_placeholder(&value) {
+        return value;
+    }
+    let lookup = |key: &str| apollo_lookup(cc, cache, key).map(|v| v.config_value);
+    apollo_resolve_placeholder(&value, &lookup)
+}
+
+/// 比较新旧生效配置区别
+pub(crate) fn apollo_effective_diff(new_view: &HashMap<String, ApolloConfigItem>, old_view: &HashMap<String, ApolloConfigItem>) -> Vec<ApolloChangeEvent> {
     let mut res = Vec::new();
-    for (key, value) in &old_cfg.configurations {
-        let v_new = new_cfg.configurations.get(key);
+    for (key, item) in old_view {
+        let v_new = new_view.get(key);
         if v_new.is_none() {
             let event = ApolloChangeEvent{
-                namespace: old_cfg.namespace.clone(),
+                namespace: item.namespace.clone(),
                 key: key.clone(),
                 new_value: "".to_string(),
                 action: ApolloChangeAction::DELETE,
@@ -510,29 +1681,26 @@ fn apollo_namespace_diff(new_cfg: &apollo_namespace, old_cfg: &apollo_namespace)
             continue;
         }
         let v_new = v_new.unwrap();
-        if v_new != value {
+        if v_new.config_value != item.config_value {
             let event = ApolloChangeEvent{
-                namespace: old_cfg.namespace.clone(),
+                namespace: v_new.namespace.clone(),
                 key: key.clone(),
-                new_value: v_new.to_string(),
+                new_value: v_new.config_value.clone(),
                 action: ApolloChangeAction::UPDATE,
             };
             res.push(event);
             continue;
         }
-        continue;
     }
-    for (key, value) in &new_cfg.configurations {
-        let v_old = old_cfg.configurations.get(key);
-        if v_old.is_none() {
+    for (key, item) in new_view {
+        if !old_view.contains_key(key) {
             let event = ApolloChangeEvent{
-                namespace: old_cfg.namespace.clone(),
+                namespace: item.namespace.clone(),
                 key: key.clone(),
-                new_value: value.to_string(),
+                new_value: item.config_value.clone(),
                 action: ApolloChangeAction::ADD,
             };
             res.push(event);
-            continue;
         }
     }
     return res;
@@ -1,9 +1,19 @@
+// 沿用 is_err()/unwrap() 和显式 return 的写法
+#![allow(
+    clippy::unnecessary_unwrap,
+    clippy::needless_return,
+    clippy::len_zero,
+    clippy::question_mark,
+    clippy::let_and_return,
+    clippy::type_complexity,
+)]
+
 use std::{collections::HashMap};
 
 use reqwest::{Client, StatusCode};
 use serde::{Serialize, Deserialize};
 
-use super::error::ApolloError;
+use super::{error::ApolloError, trace::record_field};
 
 
 #[allow(non_camel_case_types)]
@@ -23,6 +33,7 @@ impl ApolloOpenApiClient {
         ApolloOpenApiClient { token: token.to_string(), portal_url: base_url, client: cli }
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "apollo.openapi", skip_all, fields(portal = %self.portal_url, method = %method, uri = uri, status = tracing::field::Empty)))]
     async fn exec_req<T: for<'a> serde::Deserialize<'a>>(&self, uri: &str, method: reqwest::Method, body: Option<String>, arg: T) -> Result<T, ApolloError> {
         let url = format!("{}{}", self.portal_url, uri);
         let mut req_builder = self.client.get(&url);
@@ -44,26 +55,32 @@ impl ApolloOpenApiClient {
         if resp.is_err() {
             let err = resp.unwrap_err();
             log::error!("open api {} exec failed...error: {:?}", uri, err);
-            return Err(ApolloError::new(330990, "open api exec failed...".to_string()));
+            return Err(ApolloError::Transport(err));
         }
         let resp = resp.unwrap();
         let status = resp.status();
+        record_field!("status", status.as_u16());
         let text = resp.text().await;
         if text.is_err() {
             let err = text.unwrap_err();
             log::error!("read open api response failed...error: {:?}", err);
-            return Err(ApolloError::new(330991, "read response failed..".to_string()));
+            return Err(ApolloError::Transport(err));
         }
         let text = text.unwrap();
 
         if status != StatusCode::OK {
             let res_de: Result<BadResponse, serde_json::Error> = serde_json::from_str(&text);
-            if res_de.is_err() {
-                log::error!("apollo portal request exec failed....unexpected response:{}", &text);
-                return Err(ApolloError::new(330991, "unexpected response".to_string()));
+            let message = match res_de {
+                Ok(resp) => resp.message,
+                Err(_) => {
+                    log::error!("apollo portal request exec failed....unexpected response:{}", &text);
+                    text
+                }
+            };
+            if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+                return Err(ApolloError::Unauthorized { status: status.as_u16(), message });
             }
-            let resp = res_de.unwrap();
-            return Err(ApolloError::new(330992, resp.message));
+            return Err(ApolloError::HttpStatus { status: status.as_u16(), message });
         }
 
         if text.len() == 0 {
@@ -73,12 +90,12 @@ impl ApolloOpenApiClient {
 
         let res_de: Result<T, serde_json::Error> = serde_json::from_str(&text);
         if res_de.is_err() {
-            let err = res_de.err();
-            println!("{:?}", err);
-            return Err(ApolloError::new(330993, "response deserialize failed".to_string()));
+            let err = res_de.err().unwrap();
+            log::error!("open api {} response deserialize failed...response: {}, error: {:?}", uri, &text, err);
+            return Err(ApolloError::Deserialize(err));
         }
         
-        Ok(res_de.unwrap())
+        Ok(res_de.ok().unwrap())
 
     }
 
@@ -93,7 +110,7 @@ impl ApolloOpenApiClient {
     pub async fn get_apps(&self) -> Result<Vec<AppInfo>, ApolloError> {
         let uri = "/openapi/v1/apps";
         let arg: Vec<AppInfo> = Vec::new();
-        return self.exec_req(&uri, reqwest::Method::GET, None, arg).await;
+        return self.exec_req(uri, reqwest::Method::GET, None, arg).await;
     }
 
     ///获取集群接口
@@ -0,0 +1,249 @@
+//! 同步接口, 供非async程序使用. 内部持有独立的tokio runtime, 不能在async上下文中调用或drop
+
+use std::{collections::HashMap, sync::Arc};
+
+use super::{
+    apollo_config_client::{self as config, ApolloAccessReport, ApolloChangeEvent, ApolloClientStatus, ApolloConfigItem, ApolloLazyLoad, ApolloRelease, ApolloReleaseRejection},
+    apollo_openapi_client::{
+        self as openapi, AppInfo, ClusterInfo, ConfigItem, CreateClusterReq, CreateConfigItemReq, CreateNamespaceReq, CurrentEditor,
+        EnvCluster, NamespaceCreated, NamespaceDetail, ReleaseConfigReq, ReleaseConfigResp, UpdateConfigItemReq,
+    },
+    error::ApolloError,
+    signer::{AccessKeySource, RequestSigner},
+};
+
+fn runtime() -> Result<tokio::runtime::Runtime, ApolloError> {
+    tokio::runtime::Builder::new_current_thread().enable_all().build().map_err(ApolloError::Io)
+}
+
+/// 同步版本的 config client, 配置监听仍在后台线程进行
+pub struct ApolloConfigClient {
+    inner: config::ApolloConfigClient,
+    rt: tokio::runtime::Runtime,
+}
+
+/// 参数同 `apollo_config_client::new`
+pub fn new(meta_server: Vec<&str>, app_id: &str, cluster_name: &str, namespaces: Option<Vec<&str>>, secret: Option<&str>) -> Result<ApolloConfigClient, ApolloError> {
+    let rt = runtime()?;
+    let inner = rt.block_on(config::new(meta_server, app_id, cluster_name, namespaces, secret))?;
+    Ok(ApolloConfigClient { inner, rt })
+}
+
+/// 参数同 `apollo_config_client::new_with_optional_namespaces`
+pub fn new_with_optional_namespaces(meta_server: Vec<&str>, app_id: &str, cluster_name: &str, required: Vec<&str>, optional: Vec<&str>, secret: Option<&str>) -> Result<ApolloConfigClient, ApolloError> {
+    let rt = runtime()?;
+    let inner = rt.block_on(config::new_with_optional_namespaces(meta_server, app_id, cluster_name, required, optional, secret))?;
+    Ok(ApolloConfigClient { inner, rt })
+}
+
+impl ApolloConfigClient {
+    pub fn get_config(&self, key: &str) -> Option<ApolloConfigItem> {
+        self.inner.get_config(key)
+    }
+
+    pub fn get_config_from_namespace(&self, key: &str, namespace: &str) -> Option<ApolloConfigItem> {
+        self.inner.get_config_from_namespace(key, namespace)
+    }
+
+    pub fn enable_placeholder(&self, enable: bool) {
+        self.inner.enable_placeholder(enable)
+    }
+
+    pub fn keys(&self) -> Vec<String> {
+        self.inner.keys()
+    }
+
+    pub fn keys_in_namespace(&self, namespace: &str) -> Option<Vec<String>> {
+        self.inner.keys_in_namespace(namespace)
+    }
+
+    pub fn get_by_prefix(&self, prefix: &str) -> HashMap<String, ApolloConfigItem> {
+        self.inner.get_by_prefix(prefix)
+    }
+
+    pub fn snapshot(&self) -> HashMap<String, ApolloConfigItem> {
+        self.inner.snapshot()
+    }
+
+    pub fn enable_access_tracking(&self, enable: bool) {
+        self.inner.enable_access_tracking(enable)
+    }
+
+    pub fn access_report(&self) -> ApolloAccessReport {
+        self.inner.access_report()
+    }
+
+    pub fn set_env_override(&self, prefix: Option<&str>) {
+        self.inner.set_env_override(prefix)
+    }
+
+    pub fn set_override_file(&self, path: Option<&str>) -> Option<ApolloError> {
+        self.inner.set_override_file(path)
+    }
+
+    /// 阻塞直到所有key都能取到值, 超时返回`ApolloError::Timeout`, 期间client被关闭返回`ApolloError::Closed`
+    pub fn wait_for(&self, keys: &[&str], timeout: std::time::Duration) -> Result<(), ApolloError> {
+        self.rt.block_on(self.inner.wait_for(keys, timeout))
+    }
+
+    pub fn listen_namespace(&self, namespace: &str) -> Option<ApolloError> {
+        self.rt.block_on(self.inner.listen_namespace(namespace))
+    }
+
+    pub fn listen_namespace_with_priority(&self, namespace: &str, priority: i32) -> Option<ApolloError> {
+        self.rt.block_on(self.inner.listen_namespace_with_priority(namespace, priority))
+    }
+
+    pub fn add_release_validator<F>(&self, namespace: Option<&str>, validator: F)
+    where
+        F: Fn(&ApolloRelease) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.inner.add_release_validator(namespace, validator)
+    }
+
+    pub fn add_key_validator<F>(&self, namespace: Option<&str>, key: &str, validator: F)
+    where
+        F: Fn(&str) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.inner.add_key_validator(namespace, key, validator)
+    }
+
+    pub fn freeze_namespace(&self, namespace: &str, release_key: Option<&str>) -> bool {
+        self.inner.freeze_namespace(namespace, release_key)
+    }
+
+    pub fn unfreeze_namespace(&self, namespace: &str) -> bool {
+        self.inner.unfreeze_namespace(namespace)
+    }
+
+    pub fn set_lazy_load(&self, mode: ApolloLazyLoad) {
+        self.inner.set_lazy_load(mode)
+    }
+
+    pub fn tolerate_missing_namespace(&self, tolerate: bool) {
+        self.inner.tolerate_missing_namespace(tolerate)
+    }
+
+    pub fn set_namespace_priority(&self, namespace: &str, priority: i32) -> bool {
+        self.inner.set_namespace_priority(namespace, priority)
+    }
+
+    pub fn namespace_order(&self) -> Vec<(String, i32)> {
+        self.inner.namespace_order()
+    }
+
+    pub fn unlisten_namespace(&self, namespace: &str) -> bool {
+        self.inner.unlisten_namespace(namespace)
+    }
+
+    pub fn close(&self) {
+        self.inner.close()
+    }
+
+    pub fn set_access_keys(&self, source: AccessKeySource) {
+        self.inner.set_access_keys(source)
+    }
+
+    pub fn set_request_signer(&self, signer: Arc<dyn RequestSigner>) {
+        self.inner.set_request_signer(signer)
+    }
+
+    pub fn status(&self) -> ApolloClientStatus {
+        self.inner.status()
+    }
+
+    pub fn fetch_change_event(&self) -> Option<Vec<ApolloChangeEvent>> {
+        self.inner.fetch_change_event()
+    }
+
+    /// 同步读取时使用receiver的`blocking_recv`
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<Vec<ApolloChangeEvent>> {
+        self.inner.subscribe()
+    }
+
+    pub fn subscribe_rejections(&self) -> tokio::sync::broadcast::Receiver<ApolloReleaseRejection> {
+        self.inner.subscribe_rejections()
+    }
+
+    pub fn subscribe_debounced(&self, window: std::time::Duration) -> tokio::sync::broadcast::Receiver<Vec<ApolloChangeEvent>> {
+        self.inner.subscribe_debounced(window)
+    }
+
+    /// 内部的async client
+    pub fn as_async(&self) -> &config::ApolloConfigClient {
+        &self.inner
+    }
+}
+
+/// 同步版本的 openapi client
+pub struct ApolloOpenApiClient {
+    inner: openapi::ApolloOpenApiClient,
+    rt: tokio::runtime::Runtime,
+}
+
+impl ApolloOpenApiClient {
+    pub fn new(portal_url: &str, token: &str) -> Result<ApolloOpenApiClient, ApolloError> {
+        let rt = runtime()?;
+        Ok(ApolloOpenApiClient { inner: openapi::ApolloOpenApiClient::new(portal_url, token), rt })
+    }
+
+    pub fn get_app_env_clusters(&self, app_id: &str) -> Result<Vec<EnvCluster>, ApolloError> {
+        self.rt.block_on(self.inner.get_app_env_clusters(app_id))
+    }
+
+    pub fn get_apps(&self) -> Result<Vec<AppInfo>, ApolloError> {
+        self.rt.block_on(self.inner.get_apps())
+    }
+
+    pub fn get_cluster(&self, env: &str, app_id: &str, cluster: &str) -> Result<ClusterInfo, ApolloError> {
+        self.rt.block_on(self.inner.get_cluster(env, app_id, cluster))
+    }
+
+    pub fn create_cluster(&self, env: &str, create_req: &CreateClusterReq) -> Result<ClusterInfo, ApolloError> {
+        self.rt.block_on(self.inner.create_cluster(env, create_req))
+    }
+
+    pub fn get_all_namespaces(&self, env: &str, app_id: &str, cluster: &str) -> Result<Vec<NamespaceDetail>, ApolloError> {
+        self.rt.block_on(self.inner.get_all_namespaces(env, app_id, cluster))
+    }
+
+    pub fn get_namespace_detail(&self, env: &str, app_id: &str, cluster: &str, namespace: &str) -> Result<NamespaceDetail, ApolloError> {
+        self.rt.block_on(self.inner.get_namespace_detail(env, app_id, cluster, namespace))
+    }
+
+    pub fn create_namespace(&self, create_req: &CreateNamespaceReq) -> Result<NamespaceCreated, ApolloError> {
+        self.rt.block_on(self.inner.create_namespace(create_req))
+    }
+
+    pub fn get_current_editor(&self, env: &str, app_id: &str, cluster: &str, namespace: &str) -> Result<CurrentEditor, ApolloError> {
+        self.rt.block_on(self.inner.get_current_editor(env, app_id, cluster, namespace))
+    }
+
+    pub fn get_config(&self, env: &str, app_id: &str, cluster: &str, namespace: &str, key: &str) -> Result<ConfigItem, ApolloError> {
+        self.rt.block_on(self.inner.get_config(env, app_id, cluster, namespace, key))
+    }
+
+    pub fn create_config(&self, env: &str, app_id: &str, cluster: &str, namespace: &str, create_req: &CreateConfigItemReq) -> Result<ConfigItem, ApolloError> {
+        self.rt.block_on(self.inner.create_config(env, app_id, cluster, namespace, create_req))
+    }
+
+    pub fn update_config(&self, env: &str, app_id: &str, cluster: &str, namespace: &str, create_req: &UpdateConfigItemReq) -> Result<String, ApolloError> {
+        self.rt.block_on(self.inner.update_config(env, app_id, cluster, namespace, create_req))
+    }
+
+    pub fn delete_config(&self, env: &str, app_id: &str, cluster: &str, namespace: &str, key: &str, operator: &str) -> Result<String, ApolloError> {
+        self.rt.block_on(self.inner.delete_config(env, app_id, cluster, namespace, key, operator))
+    }
+
+    pub fn release_config(&self, env: &str, app_id: &str, cluster: &str, namespace: &str, req: &ReleaseConfigReq) -> Result<ReleaseConfigResp, ApolloError> {
+        self.rt.block_on(self.inner.release_config(env, app_id, cluster, namespace, req))
+    }
+
+    pub fn get_namespace_latest_release(&self, env: &str, app_id: &str, cluster: &str, namespace: &str) -> Result<ReleaseConfigResp, ApolloError> {
+        self.rt.block_on(self.inner.get_namespace_latest_release(env, app_id, cluster, namespace))
+    }
+
+    pub fn rollback_release(&self, env: &str, release_id: &str, operator: &str) -> Result<String, ApolloError> {
+        self.rt.block_on(self.inner.rollback_release(env, release_id, operator))
+    }
+}
@@ -0,0 +1,103 @@
+//! `config` crate 适配, 开启 `config` feature 后可用
+//!
+//! `ApolloSource` 作为 `config::Source` 加入 `config::ConfigBuilder`, 带点号的key(`db.host`)按路径展开为嵌套结构.
+//! `ConfigReloader` 在收到变更事件后重新构建 `config::Config`, 包括被其他namespace遮盖的配置的变更
+
+use std::{fmt, sync::{Arc, Mutex}};
+
+use super::{apollo_config_client::ApolloConfigClient, source::{spawn_events_handler, BackgroundTask}};
+
+/// `config::Source` 实现, 值均为字符串, 由 `config` 在反序列化时转换类型
+#[derive(Clone)]
+pub struct ApolloSource {
+    client: ApolloConfigClient,
+    namespaces: Option<Vec<String>>,
+}
+
+impl ApolloSource {
+    /// 使用所有生效配置, 与 `get_config` 的结果一致(包括环境变量和本地覆盖文件)
+    pub fn new(client: ApolloConfigClient) -> ApolloSource {
+        ApolloSource { client, namespaces: None }
+    }
+
+    /// 只使用指定的namespace, 同一个key按namespace优先级取值, 未监听的namespace被忽略
+    pub fn with_namespaces(client: ApolloConfigClient, namespaces: &[&str]) -> ApolloSource {
+        ApolloSource { client, namespaces: Some(namespaces.iter().map(|ns| ns.to_string()).collect()) }
+    }
+}
+
+impl fmt::Debug for ApolloSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = self.client.status();
+        f.debug_struct("ApolloSource")
+            .field("app_id", &status.app_id)
+            .field("cluster", &status.cluster)
+            .field("namespaces", &self.namespaces)
+            .finish()
+    }
+}
+
+impl config::Source for ApolloSource {
+    fn clone_into_box(&self) -> Box<dyn config::Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> Result<config::Map<String, config::Value>, config::ConfigError> {
+        let status = self.client.status();
+        let origin = |namespace: &str| format!("apollo {}/{}/{}", status.app_id, status.cluster, namespace);
+        let mut res = config::Map::new();
+
+        if self.namespaces.is_none() {
+            for (key, item) in self.client.snapshot() {
+                let value = config::Value::new(Some(&origin(&item.namespace)), item.config_value);
+                res.insert(key, value);
+            }
+            return Ok(res);
+        }
+
+        let namespaces = self.namespaces.as_ref().unwrap();
+        //namespace_order 按优先级从高到低, 倒序插入使高优先级覆盖低优先级
+        for (namespace, _) in self.client.namespace_order().iter().rev() {
+            if !namespaces.contains(namespace) {
+                continue;
+            }
+            for key in self.client.keys_in_namespace(namespace).unwrap_or_default() {
+                if let Some(item) = self.client.get_config_from_namespace_untracked(&key, namespace) {
+                    res.insert(key, config::Value::new(Some(&origin(namespace)), item.config_value));
+                }
+            }
+        }
+        Ok(res)
+    }
+}
+
+/// 收到变更事件后重新构建的 `config::Config`
+pub struct ConfigReloader {
+    current: Arc<Mutex<Arc<config::Config>>>,
+    _watcher: BackgroundTask,
+}
+
+impl ConfigReloader {
+    /// 立即构建一次, 构建失败时返回错误. 之后每次收到`subscribe`或`subscribe_namespaces`的变更事件时在后台任务中重新构建,
+    /// `ApolloSource::with_namespaces`中被更高优先级namespace遮盖的配置变更时同样会重新构建.
+    /// 失败时记录日志并继续使用旧的配置. ConfigReloader drop后后台任务退出
+    pub fn watch<F>(client: &ApolloConfigClient, build: F) -> Result<ConfigReloader, config::ConfigError>
+    where
+        F: Fn() -> Result<config::Config, config::ConfigError> + Send + 'static,
+    {
+        let receivers = vec![client.subscribe(), client.subscribe_namespaces()];
+        let current = Arc::new(Mutex::new(Arc::new(build()?)));
+        let current_2 = current.clone();
+
+        let watcher = spawn_events_handler(receivers, move |_| match build() {
+            Ok(cfg) => *current_2.lock().unwrap() = Arc::new(cfg),
+            Err(err) => log::error!("rebuild config after apollo change failed, keep the old one. error: {:?}", err),
+        });
+        Ok(ConfigReloader { current, _watcher: watcher })
+    }
+
+    /// 最近一次构建成功的配置
+    pub fn config(&self) -> Arc<config::Config> {
+        self.current.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,142 @@
+//! 声明式配置结构体, 配合 `derive` feature 提供的 `#[derive(ApolloConfig)]` 使用
+//!
+//! ```ignore
+//! #[derive(ApolloConfig)]
+//! #[apollo(namespace = "application", validate = "check_db")]
+//! struct DbConfig {
+//!     #[apollo(namespace = "db", key = "pool.size", default = 10)]
+//!     pool_size: u32,
+//!     #[apollo(key = "db.url")]
+//!     url: String,
+//!     timeout_ms: Option<u64>,
+//! }
+//! ```
+//!
+//! 字段的key默认为字段名, namespace默认为结构体上声明的namespace, 都未声明时按 `get_config` 的优先级取值.
+//! `Option<T>` 字段可以缺失, 其他字段缺失且没有default时加载失败. 值通过 `FromStr` 解析
+
+use std::{fmt::Display, str::FromStr, sync::{Arc, Mutex}};
+
+use super::{apollo_config_client::{ApolloChangeEvent, ApolloConfigClient}, error::ApolloError, source::{spawn_events_handler, BackgroundTask}};
+
+pub trait ApolloConfig: Sized {
+    /// 读取并解析所有字段, 不做结构体级别的校验
+    fn load_fields(client: &ApolloConfigClient) -> Result<Self, ApolloError>;
+
+    /// 结构体级别的校验, 对应 `#[apollo(validate = "path")]`
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// 重新加载后调用, 对应 `#[apollo(on_reload = "path")]`
+    fn reloaded(&self, _old: &Self) {}
+
+    /// 用到的 (namespace, key)
+    fn keys() -> Vec<(Option<&'static str>, &'static str)>;
+
+    /// 加载并校验
+    fn load(client: &ApolloConfigClient) -> Result<Self, ApolloError> {
+        let res = Self::load_fields(client)?;
+        res.validate().map_err(|message| ApolloError::InvalidConfig { key: None, message })?;
+        Ok(res)
+    }
+}
+
+fn raw_value(client: &ApolloConfigClient, namespace: Option<&str>, key: &str) -> Option<String> {
+    let item = match namespace {
+        Some(ns) => client.get_config_from_namespace(key, ns),
+        None => client.get_config(key),
+    };
+    item.map(|item| item.config_value)
+}
+
+fn parse<T>(key: &str, value: &str) -> Result<T, ApolloError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match value.parse::<T>() {
+        Ok(res) => Ok(res),
+        Err(err) => {
+            let message = format!("can not parse `{}`: {}", value, err);
+            Err(ApolloError::InvalidConfig { key: Some(key.to_string()), message })
+        }
+    }
+}
+
+/// 必填字段, default 为缺失时使用的原始值
+#[doc(hidden)]
+pub fn required<T>(client: &ApolloConfigClient, namespace: Option<&str>, key: &str, default: Option<&str>) -> Result<T, ApolloError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = raw_value(client, namespace, key).or(default.map(|d| d.to_string()));
+    if value.is_none() {
+        return Err(ApolloError::InvalidConfig { key: Some(key.to_string()), message: "missing".to_string() });
+    }
+    parse(key, &value.unwrap())
+}
+
+/// `Option<T>` 字段
+#[doc(hidden)]
+pub fn optional<T>(client: &ApolloConfigClient, namespace: Option<&str>, key: &str, default: Option<&str>) -> Result<Option<T>, ApolloError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = raw_value(client, namespace, key).or(default.map(|d| d.to_string()));
+    if value.is_none() {
+        return Ok(None);
+    }
+    parse(key, &value.unwrap()).map(Some)
+}
+
+/// 字段级别的校验, 对应字段上的 `#[apollo(validate = "path")]`
+#[doc(hidden)]
+pub fn validate_field<T>(key: &str, value: &T, f: fn(&T) -> Result<(), String>) -> Result<(), ApolloError> {
+    f(value).map_err(|message| ApolloError::InvalidConfig { key: Some(key.to_string()), message })
+}
+
+/// 随配置变更自动重新加载的配置结构体
+pub struct Reloadable<T> {
+    current: Arc<Mutex<Arc<T>>>,
+    _watcher: BackgroundTask,
+}
+
+impl<T: ApolloConfig + Send + Sync + 'static> Reloadable<T> {
+    /// 立即加载一次, 失败时返回错误. 之后用到的key发生变更时在后台任务中重新加载, 指定了namespace的字段
+    /// 按`subscribe_namespaces`判断, 被更高优先级namespace遮盖时同样会重新加载.
+    /// 加载或校验失败时记录日志并保留旧值. Reloadable drop后后台任务退出
+    pub fn watch(client: &ApolloConfigClient) -> Result<Reloadable<T>, ApolloError> {
+        let receivers = vec![client.subscribe(), client.subscribe_namespaces()];
+        let current = Arc::new(Mutex::new(Arc::new(T::load(client)?)));
+        let current_2 = current.clone();
+        let client = client.clone();
+        let keys = T::keys();
+
+        let watcher = spawn_events_handler(receivers, move |events| {
+            //丢失了事件时无法判断, 直接重新加载
+            if let Some(events) = events {
+                let used = |e: &ApolloChangeEvent| keys.iter().any(|(ns, key)| *key == e.key && ns.is_none_or(|ns| ns == e.namespace));
+                if !events.iter().any(used) {
+                    return;
+                }
+            }
+            match T::load(&client) {
+                Ok(new) => {
+                    let new = Arc::new(new);
+                    let old = std::mem::replace(&mut *current_2.lock().unwrap(), new.clone());
+                    new.reloaded(&old);
+                }
+                Err(err) => log::error!("reload config struct failed, keep the old one. error: {}", err),
+            }
+        });
+        Ok(Reloadable { current, _watcher: watcher })
+    }
+
+    /// 最近一次加载成功的值
+    pub fn get(&self) -> Arc<T> {
+        self.current.lock().unwrap().clone()
+    }
+}
@@ -1,24 +1,86 @@
+/// config client 和 openapi client 共用的错误类型
 #[derive(Debug)]
-pub struct ApolloError {
-    pub code: usize,
-    pub msg: String,
-    // source_error: dyn std::error::Error,
+#[non_exhaustive]
+pub enum ApolloError {
+    /// 无法从meta server获取可用的服务地址
+    Discovery(String),
+    /// 请求发送或响应读取失败
+    Transport(reqwest::Error),
+    /// 服务端返回了非预期的状态码
+    HttpStatus { status: u16, message: String },
+    /// 签名校验失败或token无权限(401/403)
+    Unauthorized { status: u16, message: String },
+    /// namespace 不存在(404)
+    NamespaceNotFound { namespace: String },
+    /// 响应反序列化失败
+    Deserialize(serde_json::Error),
+    /// 本地文件读取失败
+    Io(std::io::Error),
+    /// 同一个namespace被并发监听
+    ConcurrentListen { namespace: String },
+    /// client 已关闭
+    Closed,
+    /// 等待超时
+    Timeout,
+    /// 配置缺失, 无法解析或校验未通过, key为None时是结构体级别的校验
+    InvalidConfig { key: Option<String>, message: String },
 }
 
 impl std::fmt::Display for ApolloError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "CustomError is here!")
+        match self {
+            ApolloError::Discovery(msg) => write!(f, "apollo server discovery failed: {}", msg),
+            ApolloError::Transport(err) => write!(f, "apollo request failed: {}", err),
+            ApolloError::HttpStatus { status, message } => write!(f, "apollo server responded {}: {}", status, message),
+            ApolloError::Unauthorized { status, message } => write!(f, "apollo request unauthorized ({}): {}", status, message),
+            ApolloError::NamespaceNotFound { namespace } => write!(f, "apollo namespace {} not found", namespace),
+            ApolloError::Deserialize(err) => write!(f, "apollo response deserialize failed: {}", err),
+            ApolloError::Io(err) => write!(f, "io error: {}", err),
+            ApolloError::ConcurrentListen { namespace } => write!(f, "concurrent load namespace {}", namespace),
+            ApolloError::Closed => write!(f, "apollo client has closed"),
+            ApolloError::Timeout => write!(f, "apollo operation timed out"),
+            ApolloError::InvalidConfig { key: Some(key), message } => write!(f, "invalid config {}: {}", key, message),
+            ApolloError::InvalidConfig { key: None, message } => write!(f, "invalid config: {}", message),
+        }
     }
 }
 
 impl std::error::Error for ApolloError {
-    // fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-    //     // Some(&self.err)
-    // }
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ApolloError::Transport(err) => Some(err),
+            ApolloError::Deserialize(err) => Some(err),
+            ApolloError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
 }
 
 impl ApolloError {
-    pub fn new(code: usize, msg: String) -> ApolloError {
-        ApolloError { code: code, msg: msg} //, source_error: source }
+    /// 是否是暂时性错误, 重试(或换一个服务地址)可能成功
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ApolloError::Discovery(_) | ApolloError::Transport(_) | ApolloError::Timeout => true,
+            ApolloError::HttpStatus { status, .. } => *status >= 500 || *status == 429,
+            _ => false,
+        }
+    }
+}
+
+impl From<reqwest::Error> for ApolloError {
+    fn from(err: reqwest::Error) -> Self {
+        ApolloError::Transport(err)
     }
-}
\ No newline at end of file
+}
+
+impl From<serde_json::Error> for ApolloError {
+    fn from(err: serde_json::Error) -> Self {
+        ApolloError::Deserialize(err)
+    }
+}
+
+impl From<std::io::Error> for ApolloError {
+    fn from(err: std::io::Error) -> Self {
+        ApolloError::Io(err)
+    }
+}
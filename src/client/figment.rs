@@ -0,0 +1,138 @@
+//! figment Provider, 开启 `figment` feature 后可用
+//!
+//! 带点号的key(`db.host`)展开为嵌套结构, 值按figment `Env` provider 的规则解析为数字/布尔等类型.
+//! 使用 `merge_namespaces` 时每个namespace是一个独立的provider, 错误信息中会指出值来自哪个namespace.
+//! `FigmentReloader` 在收到变更事件后重新构建 `Figment`, 包括被其他namespace遮盖的配置的变更
+
+use figment::{
+    value::{Dict, Map, Tag, Value},
+    Error, Figment, Metadata, Profile, Provider, Source,
+};
+
+use std::sync::{Arc, Mutex};
+
+use super::{apollo_config_client::ApolloConfigClient, source::{spawn_events_handler, BackgroundTask}};
+
+pub struct ApolloProvider {
+    client: ApolloConfigClient,
+    /// None 时为所有生效配置
+    namespace: Option<String>,
+    profile: Profile,
+}
+
+impl ApolloProvider {
+    /// 所有生效配置, 与 `get_config` 的结果一致(包括环境变量和本地覆盖文件)
+    pub fn new(client: ApolloConfigClient) -> ApolloProvider {
+        ApolloProvider { client, namespace: None, profile: Profile::Default }
+    }
+
+    /// 只包含一个namespace的配置
+    pub fn namespace(client: ApolloConfigClient, namespace: &str) -> ApolloProvider {
+        ApolloProvider { client, namespace: Some(namespace.to_string()), profile: Profile::Default }
+    }
+
+    /// 配置放在哪个profile下, 默认为 `Profile::Default`
+    pub fn profile<P: Into<Profile>>(mut self, profile: P) -> ApolloProvider {
+        self.profile = profile.into();
+        self
+    }
+
+    fn configurations(&self) -> Vec<(String, String)> {
+        if self.namespace.is_none() {
+            return self.client.snapshot().into_iter().map(|(key, item)| (key, item.config_value)).collect();
+        }
+        let namespace = self.namespace.as_ref().unwrap();
+        let mut res = Vec::new();
+        for key in self.client.keys_in_namespace(namespace).unwrap_or_default() {
+            if let Some(item) = self.client.get_config_from_namespace_untracked(&key, namespace) {
+                res.push((key, item.config_value));
+            }
+        }
+        res
+    }
+}
+
+impl Provider for ApolloProvider {
+    fn metadata(&self) -> Metadata {
+        let status = self.client.status();
+        match &self.namespace {
+            Some(ns) => Metadata::from(
+                format!("Apollo namespace `{}`", ns),
+                Source::Custom(format!("apollo {}/{}/{}", status.app_id, status.cluster, ns)),
+            ),
+            None => Metadata::from("Apollo", Source::Custom(format!("apollo {}/{}", status.app_id, status.cluster))),
+        }
+    }
+
+    fn data(&self) -> Result<Map<Profile, Dict>, Error> {
+        let mut dict = Dict::new();
+        for (key, value) in self.configurations() {
+            //解析失败时为字符串, 错误类型为Infallible
+            let Ok(value) = value.parse::<Value>();
+            insert_nested(&mut dict, &key, value);
+        }
+        let mut res = Map::new();
+        res.insert(self.profile.clone(), dict);
+        Ok(res)
+    }
+}
+
+/// 按优先级从低到高依次merge每个已监听的namespace. profiles 指定namespace对应的profile, 未指定的使用默认profile.
+/// 被遮盖的配置变更时不产生`subscribe`的事件, 需要随配置重新merge时使用`FigmentReloader`
+pub fn merge_namespaces(figment: Figment, client: &ApolloConfigClient, profiles: &[(&str, &str)]) -> Figment {
+    let mut figment = figment;
+    for (namespace, _) in client.namespace_order().iter().rev() {
+        let mut provider = ApolloProvider::namespace(client.clone(), namespace);
+        if let Some((_, profile)) = profiles.iter().find(|(ns, _)| ns == namespace) {
+            provider = provider.profile(*profile);
+        }
+        figment = figment.merge(provider);
+    }
+    figment
+}
+
+/// `a.b.c` 展开为嵌套的dict, 同一路径既有值又有子key时保留子key
+fn insert_nested(dict: &mut Dict, key: &str, value: Value) {
+    let split = key.split_once('.');
+    if split.is_none() || split.unwrap().0.is_empty() {
+        if !matches!(dict.get(key), Some(Value::Dict(..))) {
+            dict.insert(key.to_string(), value);
+        }
+        return;
+    }
+    let (head, rest) = split.unwrap();
+    let entry = dict.entry(head.to_string()).or_insert_with(|| Value::Dict(Tag::Default, Dict::new()));
+    if !matches!(entry, Value::Dict(..)) {
+        *entry = Value::Dict(Tag::Default, Dict::new());
+    }
+    if let Value::Dict(_, child) = entry {
+        insert_nested(child, rest, value);
+    }
+}
+
+/// 收到变更事件后重新构建的 `Figment`
+pub struct FigmentReloader {
+    current: Arc<Mutex<Arc<Figment>>>,
+    _watcher: BackgroundTask,
+}
+
+impl FigmentReloader {
+    /// 立即构建一次, 之后每次收到`subscribe`或`subscribe_namespaces`的变更事件时在后台任务中重新构建,
+    /// `merge_namespaces`中被更高优先级namespace遮盖的配置变更时同样会重新构建. FigmentReloader drop后后台任务退出
+    pub fn watch<F>(client: &ApolloConfigClient, build: F) -> FigmentReloader
+    where
+        F: Fn() -> Figment + Send + 'static,
+    {
+        let receivers = vec![client.subscribe(), client.subscribe_namespaces()];
+        let current = Arc::new(Mutex::new(Arc::new(build())));
+        let current_2 = current.clone();
+
+        let watcher = spawn_events_handler(receivers, move |_| *current_2.lock().unwrap() = Arc::new(build()));
+        FigmentReloader { current, _watcher: watcher }
+    }
+
+    /// 最近一次构建的 `Figment`
+    pub fn figment(&self) -> Arc<Figment> {
+        self.current.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,83 @@
+use std::{collections::HashMap, time::SystemTime};
+
+use super::{error::ApolloError, placeholder::env_name};
+
+/// 本地覆盖配置文件, properties 格式
+#[derive(Debug)]
+pub struct OverrideFile {
+    pub path: String,
+    pub configurations: HashMap<String, String>,
+    modified: Option<SystemTime>,
+}
+
+impl OverrideFile {
+    pub fn load(path: &str) -> Result<OverrideFile, ApolloError> {
+        let content = std::fs::read_to_string(path).map_err(|err| {
+            log::error!("read override file {} failed, error: {:?}", path, err);
+            ApolloError::Io(err)
+        })?;
+        let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        Ok(OverrideFile {
+            path: path.to_string(),
+            configurations: parse_properties(&content),
+            modified,
+        })
+    }
+
+    /// 文件修改后重新加载, 返回是否有变化. 文件被删除时清空配置
+    pub fn reload_if_modified(&mut self) -> bool {
+        let meta = std::fs::metadata(&self.path);
+        if meta.is_err() {
+            if self.modified.is_none() && self.configurations.is_empty() {
+                return false;
+            }
+            log::warn!("override file {} is unreadable, clear its config", &self.path);
+            self.modified = None;
+            self.configurations.clear();
+            return true;
+        }
+        let modified = meta.unwrap().modified().ok();
+        if modified.is_some() && modified == self.modified {
+            return false;
+        }
+        let content = match std::fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(err) => {
+                log::warn!("read override file {} failed, error: {:?}", &self.path, err);
+                return false;
+            }
+        };
+        self.modified = modified;
+        self.configurations = parse_properties(&content);
+        true
+    }
+}
+
+/// 环境变量覆盖: key `db.host` 对应环境变量 `{prefix}DB_HOST`, 返回 (环境变量名, 值)
+pub fn env_override(prefix: &str, key: &str) -> Option<(String, String)> {
+    let name = format!("{}{}", prefix, env_name(key));
+    let value = std::env::var(&name);
+    if value.is_err() {
+        return None;
+    }
+    Some((name, value.unwrap()))
+}
+
+/// 解析 properties 格式, 仅支持单行 `key=value` / `key:value`, `#` `!` 开头为注释
+pub fn parse_properties(content: &str) -> HashMap<String, String> {
+    let mut res = HashMap::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+            continue;
+        }
+        let idx = line.find(['=', ':']);
+        if idx.is_none() {
+            res.insert(line.to_string(), "".to_string());
+            continue;
+        }
+        let idx = idx.unwrap();
+        res.insert(line[..idx].trim_end().to_string(), line[idx + 1..].trim_start().to_string());
+    }
+    res
+}
@@ -1,19 +1,31 @@
+// 沿用 is_err()/unwrap() 和显式 return 的写法
+#![allow(
+    clippy::unnecessary_unwrap,
+    clippy::needless_return,
+    clippy::len_zero,
+    clippy::question_mark,
+    clippy::let_and_return,
+    clippy::type_complexity,
+    clippy::enum_variant_names,
+)]
+
 use std::{time::SystemTime, sync::{Arc, mpsc::{self, Sender, Receiver}}};
 
 use log::warn;
 use serde::{Serialize, Deserialize};
 use tokio::{task::JoinHandle, join};
 
-use super::error::ApolloError;
+use super::{error::ApolloError, metrics, trace::trace_event};
 
 #[derive(PartialEq, Eq)]
 pub enum ApolloServerEnum {
     ConfigServer,       //xx:8080
     AdminServer,        //xx:8090
+    #[allow(dead_code)]
     PortalServer,       //xx:8070, no register in eureka by default 
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MetaServer {
     server_list: Vec<String>,
 }
@@ -30,6 +42,7 @@ impl MetaServer {
         ms
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "apollo.meta_discovery", skip_all, fields(meta_servers = ?self.server_list)))]
     pub async fn get_config_servers(&self, server_kind: ApolloServerEnum) -> Result<Vec<String>, ApolloError> {
         let mut result: Vec<String> = Vec::new();
         let kind = {
@@ -61,11 +74,20 @@ impl MetaServer {
                 
                 let res = client.execute(req).await;  
                 if res.is_err() {
-                    tx1.send(Err(ApolloError::new(1212, "meta server".to_string()))).unwrap();
+                    tx1.send(Err(ApolloError::Transport(res.unwrap_err()))).unwrap();
+                    return ;
+                }
+                let res_str = res.unwrap().text().await;
+                if res_str.is_err() {
+                    tx1.send(Err(ApolloError::Transport(res_str.unwrap_err()))).unwrap();
                     return ;
                 }
-                let res_str = res.unwrap().text().await.unwrap();
-                let eureka_resp: EurekaResp = serde_json::from_str(&res_str).unwrap();
+                let eureka_resp: Result<EurekaResp, serde_json::Error> = serde_json::from_str(&res_str.unwrap());
+                if eureka_resp.is_err() {
+                    tx1.send(Err(ApolloError::Deserialize(eureka_resp.unwrap_err()))).unwrap();
+                    return ;
+                }
+                let eureka_resp = eureka_resp.unwrap();
                 for ele in &eureka_resp.applications.application {
                     if ele.name == kind {
                         for ins in &ele.instance {
@@ -104,9 +126,14 @@ impl MetaServer {
             }
         }
         
-        let end = SystemTime::now().duration_since(start).unwrap();
+        let end = SystemTime::now().duration_since(start).unwrap_or_default();
+
+        log::debug!("get {} servers from meta server in {:?}: {:?}", kind, end, result);
+        trace_event!(tracing::Level::DEBUG, kind, servers = ?result, elapsed_ms = end.as_millis() as u64, "apollo meta discovery finished");
 
-        println!("{:?} {:?}", end, result);
+        let outcome = if result.len() > 0 { "ok" } else { "empty" };
+        metrics::timed("apollo_meta_discovery_total", "apollo_meta_discovery_duration_seconds", &[("outcome", outcome)], end);
+        metrics::gauge("apollo_config_servers", &[], result.len() as f64);
 
         Ok(result)
     }
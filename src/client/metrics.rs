@@ -0,0 +1,75 @@
+//! 指标埋点, 开启 `metrics` feature 后通过 `set_metrics_recorder` 接入 Prometheus 等监控系统
+//!
+//! counter: `apollo_config_fetch_total`, `apollo_long_poll_total`, `apollo_meta_discovery_total`, `apollo_change_events_total`, `apollo_release_rejected_total`
+//! histogram(秒): `apollo_config_fetch_duration_seconds`, `apollo_long_poll_duration_seconds`, `apollo_meta_discovery_duration_seconds`
+//! gauge: `apollo_config_servers`, `apollo_namespace_cache_age_seconds`
+
+use std::time::Duration;
+
+use super::error::ApolloError;
+
+pub type Labels<'a> = &'a [(&'static str, &'a str)];
+
+/// 指标记录器, 各方法默认不做任何事
+#[cfg(feature = "metrics")]
+pub trait MetricsRecorder: Send + Sync {
+    fn increment_counter(&self, _name: &'static str, _labels: Labels, _value: u64) {}
+    fn record_histogram(&self, _name: &'static str, _labels: Labels, _value: f64) {}
+    fn set_gauge(&self, _name: &'static str, _labels: Labels, _value: f64) {}
+}
+
+#[cfg(feature = "metrics")]
+static RECORDER: std::sync::OnceLock<Box<dyn MetricsRecorder>> = std::sync::OnceLock::new();
+
+/// 设置全局的指标记录器, 只能设置一次, 重复设置返回false
+#[cfg(feature = "metrics")]
+pub fn set_metrics_recorder(recorder: Box<dyn MetricsRecorder>) -> bool {
+    RECORDER.set(recorder).is_ok()
+}
+
+#[cfg(feature = "metrics")]
+pub(crate) fn counter(name: &'static str, labels: Labels, value: u64) {
+    if let Some(r) = RECORDER.get() {
+        r.increment_counter(name, labels, value);
+    }
+}
+
+#[cfg(feature = "metrics")]
+pub(crate) fn histogram(name: &'static str, labels: Labels, value: f64) {
+    if let Some(r) = RECORDER.get() {
+        r.record_histogram(name, labels, value);
+    }
+}
+
+#[cfg(feature = "metrics")]
+pub(crate) fn gauge(name: &'static str, labels: Labels, value: f64) {
+    if let Some(r) = RECORDER.get() {
+        r.set_gauge(name, labels, value);
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn counter(_name: &'static str, _labels: Labels, _value: u64) {}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn histogram(_name: &'static str, _labels: Labels, _value: f64) {}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn gauge(_name: &'static str, _labels: Labels, _value: f64) {}
+
+/// 一次请求的计数和耗时
+pub(crate) fn timed(name: &'static str, duration_name: &'static str, labels: Labels, duration: Duration) {
+    counter(name, labels, 1);
+    histogram(duration_name, labels, duration.as_secs_f64());
+}
+
+/// 错误对应的 outcome 标签
+pub(crate) fn error_outcome(err: &ApolloError) -> &'static str {
+    match err {
+        ApolloError::NamespaceNotFound { .. } => "not_found",
+        ApolloError::Unauthorized { .. } => "unauthorized",
+        ApolloError::Deserialize(_) => "deserialize_error",
+        ApolloError::Timeout => "timeout",
+        _ => "error",
+    }
+}
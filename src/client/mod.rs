@@ -1,5 +1,22 @@
 pub mod apollo_config_client;
 pub mod error;
 pub mod apollo_openapi_client;
+pub mod metrics;
+pub mod signer;
+pub mod source;
+pub mod declare;
+#[cfg(feature = "config")]
+pub mod config_rs;
+#[cfg(feature = "figment")]
+pub mod figment;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+#[cfg(any(test, feature = "test-server"))]
+pub mod test_server;
 
 mod meta_server;
+pub(crate) mod layer;
+pub(crate) mod placeholder;
+mod trace;
@@ -0,0 +1,95 @@
+/// 解析配置值中的占位符 `${key}` / `${key:default}`
+/// 查找顺序: lookup(按namespace优先级的生效配置), 环境变量, 默认值; 都找不到或循环引用时保留原始占位符
+pub fn apollo_resolve_placeholder(value: &str, lookup: &dyn Fn(&str) -> Option<String>) -> String {
+    let mut resolving = Vec::new();
+    resolve(value, lookup, &mut resolving)
+}
+
+/// 配置值是否包含占位符
+pub fn has_placeholder(value: &str) -> bool {
+    value.contains("${")
+}
+
+/// key 对应的环境变量, 先按原样查找, 再按 `db.host` -> `DB_HOST` 的规则查找
+pub fn env_lookup(key: &str) -> Option<String> {
+    let value = std::env::var(key);
+    if value.is_ok() {
+        return value.ok();
+    }
+    std::env::var(env_name(key)).ok()
+}
+
+/// `db.host` -> `DB_HOST`
+pub fn env_name(key: &str) -> String {
+    key.to_uppercase().replace(['.', '-'], "_")
+}
+
+fn resolve(value: &str, lookup: &dyn Fn(&str) -> Option<String>, resolving: &mut Vec<String>) -> String {
+    let mut res = String::new();
+    let mut rest = value;
+
+    while let Some(start) = rest.find("${") {
+        res.push_str(&rest[..start]);
+        let end = placeholder_end(&rest[start + 2..]);
+        if end.is_none() {
+            //没有闭合的占位符, 原样保留
+            res.push_str(&rest[start..]);
+            return res;
+        }
+        let end = start + 2 + end.unwrap();
+        let body = &rest[start + 2..end];
+        let raw = &rest[start..=end];
+        rest = &rest[end + 1..];
+
+        let (key, default) = match body.split_once(':') {
+            Some((k, d)) => (k, Some(d)),
+            None => (body, None),
+        };
+
+        if resolving.iter().any(|k| k == key) {
+            log::warn!("circular placeholder reference: {} -> {}", resolving.join(" -> "), key);
+            res.push_str(raw);
+            continue;
+        }
+
+        if let Some(found) = lookup(key) {
+            resolving.push(key.to_string());
+            res.push_str(&resolve(&found, lookup, resolving));
+            resolving.pop();
+            continue;
+        }
+        if let Some(found) = env_lookup(key) {
+            res.push_str(&found);
+            continue;
+        }
+        if let Some(default) = default {
+            res.push_str(&resolve(default, lookup, resolving));
+            continue;
+        }
+        res.push_str(raw);
+    }
+    res.push_str(rest);
+    res
+}
+
+/// 返回与 `${` 匹配的 `}` 的位置, 支持嵌套 `${a:${b}}`
+fn placeholder_end(s: &str) -> Option<usize> {
+    let bytes = s.as_bytes();
+    let mut depth = 1;
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'$' && i + 1 < bytes.len() && bytes[i + 1] == b'{' {
+            depth += 1;
+            i += 2;
+            continue;
+        }
+        if bytes[i] == b'}' {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i);
+            }
+        }
+        i += 1;
+    }
+    None
+}
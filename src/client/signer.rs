@@ -0,0 +1,167 @@
+use std::{sync::{Arc, Mutex}, time::SystemTime};
+
+use crypto::mac::Mac;
+
+/// 请求签名, 返回需要添加的请求头
+pub trait RequestSigner: Send + Sync {
+    /// path_with_query 为实际发送的 path 和 query string (已编码), 与服务端校验时使用的一致
+    fn sign(&self, secret: &str, app_id: &str, path_with_query: &str) -> Vec<(String, String)>;
+}
+
+/// Apollo 默认的 HMAC-SHA1 签名
+pub struct HmacSha1Signer;
+
+impl RequestSigner for HmacSha1Signer {
+    fn sign(&self, secret: &str, app_id: &str, path_with_query: &str) -> Vec<(String, String)> {
+        let mut res = Vec::new();
+        if secret.is_empty() {
+            return res;
+        }
+        let ts = SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis();
+        let token = hmac_sha1_signature(secret, &ts.to_string(), path_with_query);
+
+        let sign = format!("Apollo {}:{}", app_id, token);
+
+        res.push(("Timestamp".to_string(), ts.to_string()));
+        res.push(("Authorization".to_string(), sign));
+        res
+    }
+}
+
+/// base64(HMAC-SHA1(secret, "{timestamp}\n{path_with_query}"))
+pub(crate) fn hmac_sha1_signature(secret: &str, timestamp: &str, path_with_query: &str) -> String {
+    let raw_str = format!("{}\n{}", timestamp, path_with_query);
+    let mut mac = crypto::hmac::Hmac::new(crypto::sha1::Sha1::new(), secret.as_bytes());
+    mac.input(raw_str.as_bytes());
+    let binding = mac.result();
+    base64::encode(binding.code())
+}
+
+/// access key secret 的来源, 轮换期间可以同时配置多个secret, 401时依次尝试
+#[derive(Clone)]
+pub enum AccessKeySource {
+    Static(Vec<String>),
+    /// 每行一个secret, `#` 开头为注释, 文件修改后自动重新读取
+    File(String),
+    /// 每次请求前调用获取secret列表
+    Callback(Arc<dyn Fn() -> Vec<String> + Send + Sync>),
+}
+
+struct AccessKeys {
+    source: AccessKeySource,
+    secrets: Vec<String>,
+    modified: Option<SystemTime>,
+    /// 最近一次校验通过的secret, 优先使用
+    active: Option<String>,
+}
+
+impl AccessKeys {
+    fn new(source: AccessKeySource) -> AccessKeys {
+        let mut keys = AccessKeys { source, secrets: Vec::new(), modified: None, active: None };
+        keys.reload();
+        keys
+    }
+
+    fn reload(&mut self) {
+        match &self.source {
+            AccessKeySource::Static(secrets) => {
+                self.secrets = secrets.clone();
+            }
+            AccessKeySource::Callback(f) => {
+                self.secrets = f();
+            }
+            AccessKeySource::File(path) => {
+                let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+                if modified.is_some() && modified == self.modified {
+                    return;
+                }
+                let content = match std::fs::read_to_string(path) {
+                    Ok(content) => content,
+                    Err(err) => {
+                        log::warn!("read access key file {} failed, keep using old secrets. error: {:?}", path, err);
+                        return;
+                    }
+                };
+                self.modified = modified;
+                self.secrets = content.lines()
+                    .map(|l| l.trim())
+                    .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                    .map(|l| l.to_string())
+                    .collect();
+            }
+        }
+        self.secrets.retain(|s| !s.is_empty());
+    }
+
+    /// 按尝试顺序返回secret, 最近校验通过的排在最前
+    fn ordered(&mut self) -> Vec<String> {
+        self.reload();
+        let mut res = self.secrets.clone();
+        if let Some(active) = &self.active {
+            if let Some(idx) = res.iter().position(|s| s == active) {
+                let s = res.remove(idx);
+                res.insert(0, s);
+            }
+        }
+        res
+    }
+}
+
+/// 签名方式和secret, 可在多个请求间共享
+#[derive(Clone)]
+pub(crate) struct ApolloSigner {
+    signer: Arc<dyn RequestSigner>,
+    keys: Arc<Mutex<AccessKeys>>,
+}
+
+impl ApolloSigner {
+    pub(crate) fn new(secret: Option<&str>) -> ApolloSigner {
+        let secrets = secret.map(|s| vec![s.to_string()]).unwrap_or_default();
+        ApolloSigner {
+            signer: Arc::new(HmacSha1Signer),
+            keys: Arc::new(Mutex::new(AccessKeys::new(AccessKeySource::Static(secrets)))),
+        }
+    }
+
+    pub(crate) fn set_signer(&mut self, signer: Arc<dyn RequestSigner>) {
+        self.signer = signer;
+    }
+
+    pub(crate) fn set_access_keys(&self, source: AccessKeySource) {
+        *self.keys.lock().unwrap() = AccessKeys::new(source);
+    }
+
+    /// 发送签名后的GET请求, 响应401时换下一个secret重试
+    pub(crate) async fn get(&self, cli: &reqwest::Client, url: &str, app_id: &str) -> Result<reqwest::Response, reqwest::Error> {
+        let secrets = self.keys.lock().unwrap().ordered();
+        if secrets.is_empty() {
+            return cli.get(url).send().await;
+        }
+
+        let mut idx = 0;
+        loop {
+            let mut req = cli.get(url).build()?;
+            let path_with_query = match req.url().query() {
+                Some(query) => format!("{}?{}", req.url().path(), query),
+                None => req.url().path().to_string(),
+            };
+            for (name, value) in self.signer.sign(&secrets[idx], app_id, &path_with_query) {
+                let name = reqwest::header::HeaderName::from_bytes(name.as_bytes());
+                let value = reqwest::header::HeaderValue::from_str(&value);
+                if let (Ok(name), Ok(value)) = (name, value) {
+                    req.headers_mut().insert(name, value);
+                }
+            }
+            let resp = cli.execute(req).await?;
+            if resp.status() == 401 && idx + 1 < secrets.len() {
+                log::warn!("apollo signature rejected, try next access key secret. path: {}", path_with_query);
+                idx += 1;
+                continue;
+            }
+            if resp.status() != 401 && idx > 0 {
+                self.keys.lock().unwrap().active = Some(secrets[idx].clone());
+            }
+            return Ok(resp);
+        }
+    }
+}
@@ -0,0 +1,385 @@
+//! 配置来源的抽象. 依赖 `ConfigSource` 而不是 `ApolloConfigClient`, 便于替换为本地文件, 固定配置或组合多个来源
+
+use std::{collections::HashMap, future::Future, pin::Pin, sync::{Arc, Mutex, OnceLock}, task::Poll, time::Duration};
+
+use tokio::sync::broadcast;
+
+use super::{
+    apollo_config_client::{apollo_effective_diff, ApolloChangeAction, ApolloChangeEvent, ApolloConfigClient, ApolloConfigItem, ApolloConfigLayer},
+    error::ApolloError,
+    layer::OverrideFile,
+};
+
+pub type ChangeReceiver = broadcast::Receiver<Vec<ApolloChangeEvent>>;
+
+pub trait ConfigSource: Send + Sync {
+    fn get(&self, key: &str) -> Option<ApolloConfigItem>;
+
+    /// 所有key, 已排序
+    fn keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self.snapshot().into_keys().collect();
+        keys.sort();
+        keys
+    }
+
+    /// 当前所有生效的配置
+    fn snapshot(&self) -> HashMap<String, ApolloConfigItem>;
+
+    /// 订阅之后的变更事件
+    fn subscribe(&self) -> ChangeReceiver;
+}
+
+impl ConfigSource for ApolloConfigClient {
+    fn get(&self, key: &str) -> Option<ApolloConfigItem> {
+        self.get_config(key)
+    }
+
+    fn keys(&self) -> Vec<String> {
+        ApolloConfigClient::keys(self)
+    }
+
+    fn snapshot(&self) -> HashMap<String, ApolloConfigItem> {
+        ApolloConfigClient::snapshot(self)
+    }
+
+    fn subscribe(&self) -> ChangeReceiver {
+        ApolloConfigClient::subscribe(self)
+    }
+}
+
+fn to_view(name: &str, layer: ApolloConfigLayer, configurations: &HashMap<String, String>) -> HashMap<String, ApolloConfigItem> {
+    configurations.iter().map(|(key, value)| {
+        let item = ApolloConfigItem {
+            config_key: key.clone(),
+            config_value: value.clone(),
+            namespace: name.to_string(),
+            layer: layer.clone(),
+        };
+        (key.clone(), item)
+    }).collect()
+}
+
+fn send_diff(tx: &broadcast::Sender<Vec<ApolloChangeEvent>>, new_view: &HashMap<String, ApolloConfigItem>, old_view: &HashMap<String, ApolloConfigItem>) {
+    let events = apollo_effective_diff(new_view, old_view);
+    if !events.is_empty() {
+        let _ = tx.send(events);
+    }
+}
+
+/// 内存中的固定配置, 配置项的namespace为创建时的name
+pub struct StaticSource {
+    name: String,
+    configurations: Mutex<HashMap<String, String>>,
+    tx: broadcast::Sender<Vec<ApolloChangeEvent>>,
+}
+
+impl StaticSource {
+    pub fn new(name: &str, configurations: HashMap<String, String>) -> StaticSource {
+        let (tx, _) = broadcast::channel(10);
+        StaticSource { name: name.to_string(), configurations: Mutex::new(configurations), tx }
+    }
+
+    /// 替换全部配置, 产生变更事件
+    pub fn replace(&self, configurations: HashMap<String, String>) {
+        let mut cfg = self.configurations.lock().unwrap();
+        let view_old = to_view(&self.name, ApolloConfigLayer::Static, &cfg);
+        *cfg = configurations;
+        send_diff(&self.tx, &to_view(&self.name, ApolloConfigLayer::Static, &cfg), &view_old);
+    }
+}
+
+impl ConfigSource for StaticSource {
+    fn get(&self, key: &str) -> Option<ApolloConfigItem> {
+        let cfg = self.configurations.lock().unwrap();
+        let value = cfg.get(key)?;
+        Some(ApolloConfigItem {
+            config_key: key.to_string(),
+            config_value: value.clone(),
+            namespace: self.name.clone(),
+            layer: ApolloConfigLayer::Static,
+        })
+    }
+
+    fn snapshot(&self) -> HashMap<String, ApolloConfigItem> {
+        to_view(&self.name, ApolloConfigLayer::Static, &self.configurations.lock().unwrap())
+    }
+
+    fn subscribe(&self) -> ChangeReceiver {
+        self.tx.subscribe()
+    }
+}
+
+/// source的后台任务共用的runtime, 第一次使用时创建
+fn background_runtime() -> &'static tokio::runtime::Runtime {
+    static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread().worker_threads(2).thread_name("apollo-source").enable_all().build().unwrap()
+    })
+}
+
+/// drop时停止的后台任务
+pub(crate) struct BackgroundTask(tokio::task::JoinHandle<()>);
+
+impl Drop for BackgroundTask {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// 在共用的runtime中执行task, task结束或返回的`BackgroundTask` drop后退出
+fn spawn_task<Fut>(task: Fut) -> BackgroundTask
+where
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    BackgroundTask(background_runtime().spawn(task))
+}
+
+type RecvFuture = Pin<Box<dyn Future<Output = (Result<Vec<ApolloChangeEvent>, broadcast::error::RecvError>, ChangeReceiver)> + Send>>;
+
+fn recv_owned(mut rx: ChangeReceiver) -> RecvFuture {
+    Box::pin(async move {
+        let res = rx.recv().await;
+        (res, rx)
+    })
+}
+
+/// 在一个后台任务中依次处理多个来源的变更事件, 丢失了事件(lagged)时参数为None. 所有来源关闭或返回值drop后退出
+pub(crate) fn spawn_events_handler<F>(receivers: Vec<ChangeReceiver>, mut handle: F) -> BackgroundTask
+where
+    F: FnMut(Option<Vec<ApolloChangeEvent>>) + Send + 'static,
+{
+    let mut pending: Vec<RecvFuture> = receivers.into_iter().map(recv_owned).collect();
+    spawn_task(async move {
+        while !pending.is_empty() {
+            let (pos, res, rx) = std::future::poll_fn(|cx| {
+                for (pos, fut) in pending.iter_mut().enumerate() {
+                    if let Poll::Ready((res, rx)) = fut.as_mut().poll(cx) {
+                        return Poll::Ready((pos, res, rx));
+                    }
+                }
+                Poll::Pending
+            }).await;
+            match res {
+                Ok(events) => handle(Some(events)),
+                Err(broadcast::error::RecvError::Lagged(_)) => handle(None),
+                Err(broadcast::error::RecvError::Closed) => {
+                    drop(pending.swap_remove(pos));
+                    continue;
+                }
+            }
+            pending[pos] = recv_owned(rx);
+        }
+    })
+}
+
+/// 在后台任务中每隔interval执行一次, 返回值drop后退出
+fn spawn_ticker<F>(interval: Duration, mut tick: F) -> BackgroundTask
+where
+    F: FnMut() + Send + 'static,
+{
+    spawn_task(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            tick();
+        }
+    })
+}
+
+/// properties 格式的本地配置文件, 每3秒及读取配置时检查文件是否被修改, 修改后重新加载并产生变更事件.
+/// 配置项的namespace为文件路径
+pub struct PropertiesFileSource {
+    inner: Arc<FileInner>,
+    _watcher: BackgroundTask,
+}
+
+struct FileInner {
+    file: Mutex<OverrideFile>,
+    tx: broadcast::Sender<Vec<ApolloChangeEvent>>,
+}
+
+impl FileInner {
+    fn reload(&self) -> bool {
+        let mut file = self.file.lock().unwrap();
+        let view_old = to_view(&file.path, ApolloConfigLayer::File, &file.configurations);
+        if !file.reload_if_modified() {
+            return false;
+        }
+        send_diff(&self.tx, &to_view(&file.path, ApolloConfigLayer::File, &file.configurations), &view_old);
+        true
+    }
+}
+
+impl PropertiesFileSource {
+    pub fn open(path: &str) -> Result<PropertiesFileSource, ApolloError> {
+        let file = OverrideFile::load(path)?;
+        let (tx, _) = broadcast::channel(10);
+        let inner = Arc::new(FileInner { file: Mutex::new(file), tx });
+        let inner_2 = inner.clone();
+        let watcher = spawn_ticker(Duration::from_secs(3), move || {
+            inner_2.reload();
+        });
+        Ok(PropertiesFileSource { inner, _watcher: watcher })
+    }
+
+    /// 文件修改后重新加载, 返回是否有变化
+    pub fn reload(&self) -> bool {
+        self.inner.reload()
+    }
+}
+
+impl ConfigSource for PropertiesFileSource {
+    fn get(&self, key: &str) -> Option<ApolloConfigItem> {
+        self.reload();
+        let file = self.inner.file.lock().unwrap();
+        let value = file.configurations.get(key)?;
+        Some(ApolloConfigItem {
+            config_key: key.to_string(),
+            config_value: value.clone(),
+            namespace: file.path.clone(),
+            layer: ApolloConfigLayer::File,
+        })
+    }
+
+    fn snapshot(&self) -> HashMap<String, ApolloConfigItem> {
+        self.reload();
+        let file = self.inner.file.lock().unwrap();
+        to_view(&file.path, ApolloConfigLayer::File, &file.configurations)
+    }
+
+    fn subscribe(&self) -> ChangeReceiver {
+        self.inner.tx.subscribe()
+    }
+}
+
+/// 组合多个来源, 排在后面的来源优先级更高. 只有生效值发生变化时才会产生变更事件
+pub struct CompositeSource {
+    inner: Arc<CompositeInner>,
+    _forwarder: BackgroundTask,
+}
+
+struct CompositeInner {
+    sources: Vec<Arc<dyn ConfigSource>>,
+    view: Mutex<HashMap<String, ApolloConfigItem>>,
+    tx: broadcast::Sender<Vec<ApolloChangeEvent>>,
+}
+
+impl CompositeInner {
+    fn merged(&self) -> HashMap<String, ApolloConfigItem> {
+        let mut view = HashMap::new();
+        for source in &self.sources {
+            view.extend(source.snapshot());
+        }
+        view
+    }
+
+    fn refresh(&self) {
+        let mut view = self.view.lock().unwrap();
+        let view_new = self.merged();
+        send_diff(&self.tx, &view_new, &view);
+        *view = view_new;
+    }
+}
+
+impl CompositeSource {
+    /// 在一个后台任务中转发所有来源的变更事件, CompositeSource drop后任务退出
+    pub fn new(sources: Vec<Arc<dyn ConfigSource>>) -> CompositeSource {
+        let (tx, _) = broadcast::channel(10);
+        let receivers: Vec<ChangeReceiver> = sources.iter().map(|s| s.subscribe()).collect();
+        let inner = Arc::new(CompositeInner { sources, view: Mutex::new(HashMap::new()), tx });
+        *inner.view.lock().unwrap() = inner.merged();
+
+        let inner_2 = inner.clone();
+        let forwarder = spawn_events_handler(receivers, move |_| inner_2.refresh());
+        CompositeSource { inner, _forwarder: forwarder }
+    }
+}
+
+impl ConfigSource for CompositeSource {
+    fn get(&self, key: &str) -> Option<ApolloConfigItem> {
+        self.inner.sources.iter().rev().find_map(|s| s.get(key))
+    }
+
+    fn snapshot(&self) -> HashMap<String, ApolloConfigItem> {
+        self.inner.merged()
+    }
+
+    fn subscribe(&self) -> ChangeReceiver {
+        self.inner.tx.subscribe()
+    }
+}
+
+/// 合并连续的多批事件, 结果为净变化: 同一个key先ADD后DELETE时不产生事件, 先DELETE后ADD时为UPDATE.
+/// 事件中没有旧值, 改回原值的多次UPDATE仍为UPDATE
+pub fn coalesce_events(batches: Vec<Vec<ApolloChangeEvent>>) -> Vec<ApolloChangeEvent> {
+    //key -> (合并前是否存在, 最后一个事件)
+    let mut merged: Vec<(bool, ApolloChangeEvent)> = Vec::new();
+    for event in batches.into_iter().flatten() {
+        if let Some(idx) = merged.iter().position(|(_, e)| e.key == event.key) {
+            merged[idx].1 = event;
+            continue;
+        }
+        let existed = !matches!(event.action, ApolloChangeAction::ADD);
+        merged.push((existed, event));
+    }
+
+    let mut res = Vec::new();
+    for (existed, event) in merged {
+        let exists = !matches!(event.action, ApolloChangeAction::DELETE);
+        let action = match (existed, exists) {
+            (false, false) => continue,
+            (false, true) => ApolloChangeAction::ADD,
+            (true, false) => ApolloChangeAction::DELETE,
+            (true, true) => ApolloChangeAction::UPDATE,
+        };
+        res.push(ApolloChangeEvent { action, ..event });
+    }
+    res
+}
+
+/// 防抖: 收到一批事件后等待window, 期间又有新事件则重新计时, 静默后把这段时间内的事件合并为一批(见`coalesce_events`)发出.
+/// 在共用的后台runtime中转发, 源关闭或返回的receiver(及其`resubscribe`)全部drop后退出
+pub fn debounce(rx: ChangeReceiver, window: Duration) -> ChangeReceiver {
+    debounce_on(background_runtime().handle(), rx, window)
+}
+
+/// 同`debounce`, 在指定的runtime中转发
+pub(crate) fn debounce_on(runtime: &tokio::runtime::Handle, rx: ChangeReceiver, window: Duration) -> ChangeReceiver {
+    let (tx, out) = broadcast::channel(10);
+    runtime.spawn(async move {
+        tokio::select! {
+            _ = tx.closed() => {},
+            _ = debounce_forward(rx, &tx, window) => {},
+        }
+    });
+    out
+}
+
+async fn debounce_forward(mut rx: ChangeReceiver, tx: &broadcast::Sender<Vec<ApolloChangeEvent>>, window: Duration) {
+    let mut closed = false;
+    while !closed {
+        let mut batches = match rx.recv().await {
+            Ok(events) => vec![events],
+            Err(broadcast::error::RecvError::Closed) => return,
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                log::warn!("debounced subscriber lagged, {} batches of change events lost", n);
+                continue;
+            }
+        };
+        loop {
+            match tokio::time::timeout(window, rx.recv()).await {
+                Err(_) => break,
+                Ok(Ok(events)) => batches.push(events),
+                Ok(Err(broadcast::error::RecvError::Lagged(n))) => log::warn!("debounced subscriber lagged, {} batches of change events lost", n),
+                Ok(Err(broadcast::error::RecvError::Closed)) => {
+                    closed = true;
+                    break;
+                }
+            }
+        }
+        let events = coalesce_events(batches);
+        if !events.is_empty() && tx.send(events).is_err() {
+            return;
+        }
+    }
+}
@@ -0,0 +1,665 @@
+//! 测试用的Apollo服务端, 数据保存在内存中, 开启 `test-server` feature 后可用
+//!
+//! 同一个地址同时作为 meta server(`/eureka/apps`, `/services/config`), config server(`/configs`, `/notifications/v2`)
+//! 和 portal(`/openapi/v1/...`) 使用. 设置了secret的app会校验请求签名, 添加了token后openapi请求会校验token.
+//! 通过 `set` / `publish` 或 openapi 修改并发布配置, 正在等待的长轮询会立即返回
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use hyper::{service::{make_service_fn, service_fn}, Body, Method, Request, Response, Server, StatusCode};
+use serde_json::json;
+
+use super::{
+    apollo_openapi_client::{CreateClusterReq, CreateConfigItemReq, CreateNamespaceReq, ReleaseConfigReq, UpdateConfigItemReq},
+    signer::hmac_sha1_signature,
+};
+
+/// (app_id, cluster, namespace)
+type NamespaceId = (String, String, String);
+
+#[derive(Default)]
+struct NamespaceState {
+    /// 编辑中(未发布)的配置
+    items: BTreeMap<String, String>,
+    /// 已发布的配置, 从未发布过时为None, 拉取配置返回404
+    released: Option<HashMap<String, String>>,
+    release_key: String,
+    notification_id: i64,
+    /// 发布历史的release id
+    releases: Vec<u64>,
+    /// 设置后拉取配置固定返回该状态码, 见`fail_configs`
+    fail_status: Option<StatusCode>,
+}
+
+struct Release {
+    namespace: NamespaceId,
+    configurations: HashMap<String, String>,
+    title: String,
+    comment: String,
+    released_by: String,
+}
+
+struct ServerState {
+    addr: SocketAddr,
+    secrets: HashMap<String, String>,
+    tokens: Vec<String>,
+    clusters: BTreeSet<(String, String)>,
+    namespaces: BTreeMap<NamespaceId, NamespaceState>,
+    releases: Vec<Release>,
+    notification_seq: i64,
+    long_poll_timeout: Duration,
+    /// (app_id, cluster) 最近一次长轮询请求中的namespace
+    polled: HashMap<(String, String), Vec<String>>,
+    /// 拉取配置的请求次数, 包括失败的请求
+    fetches: HashMap<NamespaceId, usize>,
+    /// 最近一次长轮询请求中各namespace带上的messages
+    poll_messages: HashMap<NamespaceId, serde_json::Value>,
+    /// 最近一次拉取配置请求带上的messages
+    fetch_messages: HashMap<NamespaceId, serde_json::Value>,
+}
+
+struct Shared {
+    state: Mutex<ServerState>,
+    notify: tokio::sync::Notify,
+}
+
+pub struct ApolloTestServer {
+    addr: SocketAddr,
+    shared: Arc<Shared>,
+    shutdown: Option<tokio::sync::oneshot::Sender<()>>,
+    handle: Option<std::thread::JoinHandle<()>>,
+}
+
+impl ApolloTestServer {
+    /// 在 127.0.0.1 的随机端口上启动, 服务运行在独立线程中, drop时停止
+    pub fn start() -> ApolloTestServer {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let shared = Arc::new(Shared {
+            state: Mutex::new(ServerState {
+                addr,
+                secrets: HashMap::new(),
+                tokens: Vec::new(),
+                clusters: BTreeSet::new(),
+                namespaces: BTreeMap::new(),
+                releases: Vec::new(),
+                notification_seq: 0,
+                long_poll_timeout: Duration::from_secs(60),
+                polled: HashMap::new(),
+                fetches: HashMap::new(),
+                poll_messages: HashMap::new(),
+                fetch_messages: HashMap::new(),
+            }),
+            notify: tokio::sync::Notify::new(),
+        });
+
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let shared_2 = shared.clone();
+        let handle = std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            rt.block_on(async move {
+                let make_svc = make_service_fn(move |_| {
+                    let shared = shared_2.clone();
+                    async move {
+                        Ok::<_, Infallible>(service_fn(move |req| handle_request(shared.clone(), req)))
+                    }
+                });
+                let server = Server::from_tcp(listener).unwrap().serve(make_svc);
+                tokio::select! {
+                    res = server => {
+                        if let Err(err) = res {
+                            log::error!("apollo test server stopped, error: {:?}", err);
+                        }
+                    }
+                    _ = shutdown_rx => {}
+                }
+            });
+        });
+
+        ApolloTestServer { addr, shared, shutdown: Some(shutdown_tx), handle: Some(handle) }
+    }
+
+    /// meta server, config server 和 portal 的地址, 结尾不带斜杠
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// 设置app的access key secret, 之后该app的请求必须带正确的签名
+    pub fn set_secret(&self, app_id: &str, secret: &str) {
+        self.shared.state.lock().unwrap().secrets.insert(app_id.to_string(), secret.to_string());
+    }
+
+    /// 添加openapi token, 添加后openapi请求必须带其中一个token
+    pub fn add_portal_token(&self, token: &str) {
+        self.shared.state.lock().unwrap().tokens.push(token.to_string());
+    }
+
+    /// 长轮询在没有变化时的等待时间, 默认60秒
+    pub fn set_long_poll_timeout(&self, timeout: Duration) {
+        self.shared.state.lock().unwrap().long_poll_timeout = timeout;
+    }
+
+    /// 创建namespace(未发布), 所在的cluster不存在时一并创建
+    pub fn create_namespace(&self, app_id: &str, cluster: &str, namespace: &str) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.clusters.insert((app_id.to_string(), cluster.to_string()));
+        state.namespaces.entry(namespace_id(app_id, cluster, namespace)).or_default();
+    }
+
+    /// 修改配置, `publish` 后对config client生效
+    pub fn set(&self, app_id: &str, cluster: &str, namespace: &str, key: &str, value: &str) {
+        self.create_namespace(app_id, cluster, namespace);
+        let mut state = self.shared.state.lock().unwrap();
+        let ns = state.namespaces.get_mut(&namespace_id(app_id, cluster, namespace)).unwrap();
+        ns.items.insert(key.to_string(), value.to_string());
+    }
+
+    /// 删除配置, `publish` 后对config client生效
+    pub fn remove(&self, app_id: &str, cluster: &str, namespace: &str, key: &str) {
+        let mut state = self.shared.state.lock().unwrap();
+        if let Some(ns) = state.namespaces.get_mut(&namespace_id(app_id, cluster, namespace)) {
+            ns.items.remove(key);
+        }
+    }
+
+    /// 发布namespace, 返回release key
+    pub fn publish(&self, app_id: &str, cluster: &str, namespace: &str) -> String {
+        self.create_namespace(app_id, cluster, namespace);
+        let release_key = {
+            let mut state = self.shared.state.lock().unwrap();
+            let id = namespace_id(app_id, cluster, namespace);
+            state.publish(&id, "", "", "apollo");
+            state.namespaces[&id].release_key.clone()
+        };
+        self.shared.notify.notify_waiters();
+        release_key
+    }
+
+    /// 最近一次长轮询请求中的namespace, 按请求中的顺序
+    pub fn polled_namespaces(&self, app_id: &str, cluster: &str) -> Vec<String> {
+        let state = self.shared.state.lock().unwrap();
+        state.polled.get(&(app_id.to_string(), cluster.to_string())).cloned().unwrap_or_default()
+    }
+
+    /// 最近一次长轮询请求中该namespace带上的messages, 没有带上时为None
+    pub fn polled_messages(&self, app_id: &str, cluster: &str, namespace: &str) -> Option<serde_json::Value> {
+        let state = self.shared.state.lock().unwrap();
+        state.poll_messages.get(&namespace_id(app_id, cluster, namespace)).cloned()
+    }
+
+    /// 最近一次拉取该namespace配置时带上的messages, 没有带上时为None
+    pub fn fetched_messages(&self, app_id: &str, cluster: &str, namespace: &str) -> Option<serde_json::Value> {
+        let state = self.shared.state.lock().unwrap();
+        state.fetch_messages.get(&namespace_id(app_id, cluster, namespace)).cloned()
+    }
+
+    /// 之后该namespace的拉取配置请求返回status(如404, 403, 503), None时恢复正常
+    pub fn fail_configs(&self, app_id: &str, cluster: &str, namespace: &str, status: Option<u16>) {
+        self.create_namespace(app_id, cluster, namespace);
+        let mut state = self.shared.state.lock().unwrap();
+        let ns = state.namespaces.get_mut(&namespace_id(app_id, cluster, namespace)).unwrap();
+        ns.fail_status = status.map(|status| StatusCode::from_u16(status).unwrap());
+    }
+
+    /// namespace的拉取配置请求次数, 包括失败的请求
+    pub fn config_fetches(&self, app_id: &str, cluster: &str, namespace: &str) -> usize {
+        let state = self.shared.state.lock().unwrap();
+        state.fetches.get(&namespace_id(app_id, cluster, namespace)).cloned().unwrap_or(0)
+    }
+
+    /// namespace最近一次发布的release id, 用于 `rollback_release`
+    pub fn latest_release_id(&self, app_id: &str, cluster: &str, namespace: &str) -> Option<u64> {
+        let state = self.shared.state.lock().unwrap();
+        let ns = state.namespaces.get(&namespace_id(app_id, cluster, namespace));
+        ns.and_then(|ns| ns.releases.last().cloned())
+    }
+}
+
+impl Drop for ApolloTestServer {
+    fn drop(&mut self) {
+        if let Some(tx) = self.shutdown.take() {
+            let _ = tx.send(());
+        }
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn namespace_id(app_id: &str, cluster: &str, namespace: &str) -> NamespaceId {
+    (app_id.to_string(), cluster.to_string(), namespace.to_string())
+}
+
+fn now_millis() -> u128 {
+    SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis()
+}
+
+impl ServerState {
+    fn publish(&mut self, id: &NamespaceId, title: &str, comment: &str, released_by: &str) -> u64 {
+        let configurations: HashMap<String, String> = self.namespaces[id].items.clone().into_iter().collect();
+        self.apply_release(id, configurations, title, comment, released_by)
+    }
+
+    fn apply_release(&mut self, id: &NamespaceId, configurations: HashMap<String, String>, title: &str, comment: &str, released_by: &str) -> u64 {
+        self.notification_seq += 1;
+        let release_id = self.releases.len() as u64 + 1;
+        let seq = self.notification_seq;
+        let ns = self.namespaces.get_mut(id).unwrap();
+        ns.released = Some(configurations.clone());
+        ns.release_key = format!("{}-{}", now_millis(), release_id);
+        ns.notification_id = seq;
+        ns.releases.push(release_id);
+        self.releases.push(Release {
+            namespace: id.clone(),
+            configurations,
+            title: title.to_string(),
+            comment: comment.to_string(),
+            released_by: released_by.to_string(),
+        });
+        release_id
+    }
+
+    /// 校验config server请求的签名, app未设置secret时不校验
+    fn check_signature(&self, app_id: &str, req: &Request<Body>) -> bool {
+        let secret = self.secrets.get(app_id);
+        if secret.is_none() {
+            return true;
+        }
+        let header = |name: &str| req.headers().get(name).and_then(|v| v.to_str().ok()).unwrap_or("").to_string();
+        let ts = header("Timestamp");
+        let ts_num = ts.parse::<u128>();
+        if ts_num.is_err() || now_millis().abs_diff(ts_num.unwrap()) > 60 * 1000 {
+            return false;
+        }
+        let path = req.uri().path_and_query().map(|p| p.as_str()).unwrap_or("");
+        let expected = format!("Apollo {}:{}", app_id, hmac_sha1_signature(secret.unwrap(), &ts, path));
+        header("Authorization") == expected
+    }
+
+    fn check_token(&self, req: &Request<Body>) -> bool {
+        if self.tokens.is_empty() {
+            return true;
+        }
+        let token = req.headers().get("Authorization").and_then(|v| v.to_str().ok()).unwrap_or("");
+        self.tokens.iter().any(|t| t == token)
+    }
+
+    /// 长轮询: 返回notificationId比客户端新的namespace
+    fn changed_notifications(&self, app_id: &str, cluster: &str, notifications: &[serde_json::Value]) -> Vec<serde_json::Value> {
+        let mut res = Vec::new();
+        for ele in notifications {
+            let namespace = ele["namespaceName"].as_str().unwrap_or("");
+            let client_id = ele["notificationId"].as_i64().unwrap_or(-1);
+            let ns = self.namespaces.get(&namespace_id(app_id, cluster, namespace));
+            let server_id = ns.map(|ns| ns.notification_id).filter(|id| *id > 0).unwrap_or(-1);
+            if server_id != client_id {
+                let message_key = format!("{}+{}+{}", app_id, cluster, namespace);
+                res.push(json!({
+                    "namespaceName": namespace,
+                    "notificationId": server_id,
+                    "messages": { "details": { message_key: server_id } },
+                }));
+            }
+        }
+        res
+    }
+
+    fn namespace_detail(&self, id: &NamespaceId) -> serde_json::Value {
+        let ns = &self.namespaces[id];
+        let items: Vec<serde_json::Value> = ns.items.iter().map(|(k, v)| config_item(k, v)).collect();
+        json!({
+            "appId": id.0, "clusterName": id.1, "namespaceName": id.2, "comment": "", "format": "properties", "isPublic": false,
+            "items": items,
+            "dataChangeCreatedBy": "apollo", "dataChangeLastModifiedBy": "apollo", "dataChangeCreatedTime": "", "dataChangeLastModifiedTime": "",
+        })
+    }
+
+    fn release_resp(&self, release_id: u64) -> serde_json::Value {
+        let release = &self.releases[release_id as usize - 1];
+        json!({
+            "appId": release.namespace.0, "clusterName": release.namespace.1, "namespaceName": release.namespace.2,
+            "name": release.title, "configurations": release.configurations, "comment": release.comment,
+            "dataChangeCreatedBy": release.released_by, "dataChangeLastModifiedBy": release.released_by,
+            "dataChangeCreatedTime": "", "dataChangeLastModifiedTime": "",
+        })
+    }
+}
+
+fn config_item(key: &str, value: &str) -> serde_json::Value {
+    json!({
+        "key": key, "value": value, "comment": "",
+        "dataChangeCreatedBy": "apollo", "dataChangeLastModifiedBy": "apollo", "dataChangeCreatedTime": "", "dataChangeLastModifiedTime": "",
+    })
+}
+
+fn json_resp(status: StatusCode, body: serde_json::Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json;charset=UTF-8")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn empty_resp(status: StatusCode) -> Response<Body> {
+    Response::builder().status(status).body(Body::empty()).unwrap()
+}
+
+/// 与portal相同格式的错误响应
+fn error_resp(status: StatusCode, message: &str) -> Response<Body> {
+    json_resp(status, json!({
+        "exception": "ApolloTestServerException",
+        "message": message,
+        "status": status.as_u16(),
+        "timestamp": now_millis().to_string(),
+    }))
+}
+
+/// 校验客户端带上的messages, 格式为 {"details": {"appId+cluster+namespace": notificationId}}
+fn check_messages(messages: &serde_json::Value) -> bool {
+    let details = messages["details"].as_object();
+    details.is_some() && details.unwrap().values().all(|id| id.is_i64())
+}
+
+/// 记录请求带上的messages, 没有带上时清除
+fn record_messages(records: &mut HashMap<NamespaceId, serde_json::Value>, id: NamespaceId, messages: Option<serde_json::Value>) {
+    match messages {
+        Some(messages) => records.insert(id, messages),
+        None => records.remove(&id),
+    };
+}
+
+fn query_params(req: &Request<Body>) -> HashMap<String, String> {
+    url::form_urlencoded::parse(req.uri().query().unwrap_or("").as_bytes()).into_owned().collect()
+}
+
+async fn handle_request(shared: Arc<Shared>, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let path = req.uri().path().to_string();
+    let segments: Vec<String> = path.trim_matches('/').split('/').map(|s| s.to_string()).collect();
+    let segments: Vec<&str> = segments.iter().map(|s| s.as_str()).collect();
+    log::debug!("apollo test server: {} {}", req.method(), req.uri());
+
+    let resp = match (req.method().clone(), segments.as_slice()) {
+        (Method::GET, ["eureka", "apps"]) => eureka_apps(&shared),
+        (Method::GET, ["services", "config"]) => {
+            let addr = shared.state.lock().unwrap().addr;
+            json_resp(StatusCode::OK, json!([{
+                "appName": "APOLLO-CONFIGSERVICE",
+                "instanceId": format!("{}:apollo-configservice:{}", addr.ip(), addr.port()),
+                "homepageUrl": format!("http://{}/", addr),
+            }]))
+        }
+        (Method::GET, ["configs", app_id, cluster, namespace]) => get_configs(&shared, &req, app_id, cluster, namespace),
+        (Method::GET, ["notifications", "v2"]) => notifications(&shared, &req).await,
+        (_, ["openapi", "v1", ..]) => {
+            if !shared.state.lock().unwrap().check_token(&req) {
+                error_resp(StatusCode::UNAUTHORIZED, "Unauthorized")
+            } else {
+                let method = req.method().clone();
+                let params = query_params(&req);
+                let body = hyper::body::to_bytes(req.into_body()).await.unwrap_or_default();
+                openapi(&shared, method, &segments[2..], &params, &body)
+            }
+        }
+        _ => error_resp(StatusCode::NOT_FOUND, &format!("{} not found", path)),
+    };
+    Ok(resp)
+}
+
+fn eureka_apps(shared: &Shared) -> Response<Body> {
+    let addr = shared.state.lock().unwrap().addr;
+    let instance = json!({
+        "instanceId": format!("{}:apollo-configservice:{}", addr.ip(), addr.port()),
+        "hostName": addr.ip().to_string(),
+        "app": "APOLLO-CONFIGSERVICE",
+        "ipAddr": addr.ip().to_string(),
+        "status": "UP",
+        "homePageUrl": format!("http://{}/", addr),
+        "port": { "$": addr.port(), "@enabled": "true" },
+        "securePort": { "$": 443, "@enabled": "false" },
+    });
+    json_resp(StatusCode::OK, json!({
+        "applications": {
+            "application": [{ "name": "APOLLO-CONFIGSERVICE", "instance": [instance] }],
+        },
+    }))
+}
+
+fn get_configs(shared: &Shared, req: &Request<Body>, app_id: &str, cluster: &str, namespace: &str) -> Response<Body> {
+    let mut state = shared.state.lock().unwrap();
+    *state.fetches.entry(namespace_id(app_id, cluster, namespace)).or_default() += 1;
+    if !state.check_signature(app_id, req) {
+        return error_resp(StatusCode::UNAUTHORIZED, "Unauthorized");
+    }
+    let messages: Option<serde_json::Value> = match query_params(req).get("messages") {
+        Some(messages) => match serde_json::from_str(messages) {
+            Ok(messages) if check_messages(&messages) => Some(messages),
+            _ => return error_resp(StatusCode::BAD_REQUEST, "invalid messages"),
+        },
+        None => None,
+    };
+    record_messages(&mut state.fetch_messages, namespace_id(app_id, cluster, namespace), messages);
+    let ns = state.namespaces.get(&namespace_id(app_id, cluster, namespace));
+    if let Some(status) = ns.and_then(|ns| ns.fail_status) {
+        return error_resp(status, "injected failure");
+    }
+    if ns.is_none() || ns.unwrap().released.is_none() {
+        return error_resp(StatusCode::NOT_FOUND, &format!("Could not load configurations with appId: {}, clusterName: {}, namespace: {}", app_id, cluster, namespace));
+    }
+    let ns = ns.unwrap();
+    let params = query_params(req);
+    if params.get("releaseKey") == Some(&ns.release_key) {
+        return empty_resp(StatusCode::NOT_MODIFIED);
+    }
+    json_resp(StatusCode::OK, json!({
+        "appId": app_id,
+        "cluster": cluster,
+        "namespaceName": namespace,
+        "configurations": ns.released,
+        "releaseKey": ns.release_key,
+    }))
+}
+
+async fn notifications(shared: &Shared, req: &Request<Body>) -> Response<Body> {
+    let params = query_params(req);
+    let app_id = params.get("appId").cloned().unwrap_or_default();
+    let cluster = params.get("cluster").cloned().unwrap_or_default();
+    let notifications: Result<Vec<serde_json::Value>, serde_json::Error> = serde_json::from_str(params.get("notifications").map(|s| s.as_str()).unwrap_or(""));
+    if notifications.is_err() {
+        return error_resp(StatusCode::BAD_REQUEST, "invalid notifications");
+    }
+    let notifications = notifications.unwrap();
+    let messages_valid = notifications.iter().all(|ele| ele["messages"].is_null() || check_messages(&ele["messages"]));
+    if !messages_valid {
+        return error_resp(StatusCode::BAD_REQUEST, "invalid messages");
+    }
+
+    let timeout = {
+        let mut state = shared.state.lock().unwrap();
+        if !state.check_signature(&app_id, req) {
+            return error_resp(StatusCode::UNAUTHORIZED, "Unauthorized");
+        }
+        let polled = notifications.iter().map(|ele| ele["namespaceName"].as_str().unwrap_or("").to_string()).collect();
+        state.polled.insert((app_id.clone(), cluster.clone()), polled);
+        for ele in &notifications {
+            let id = namespace_id(&app_id, &cluster, ele["namespaceName"].as_str().unwrap_or(""));
+            let messages = Some(ele["messages"].clone()).filter(|messages| !messages.is_null());
+            record_messages(&mut state.poll_messages, id, messages);
+        }
+        state.long_poll_timeout
+    };
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        let notified = shared.notify.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+
+        let changed = shared.state.lock().unwrap().changed_notifications(&app_id, &cluster, &notifications);
+        if !changed.is_empty() {
+            return json_resp(StatusCode::OK, serde_json::Value::Array(changed));
+        }
+        if tokio::time::timeout_at(deadline, notified).await.is_err() {
+            return empty_resp(StatusCode::NOT_MODIFIED);
+        }
+    }
+}
+
+fn openapi(shared: &Shared, method: Method, segments: &[&str], params: &HashMap<String, String>, body: &[u8]) -> Response<Body> {
+    let mut state = shared.state.lock().unwrap();
+    macro_rules! body {
+        ($t:ty) => {{
+            let req: Result<$t, serde_json::Error> = serde_json::from_slice(body);
+            match req {
+                Ok(req) => req,
+                Err(err) => return error_resp(StatusCode::BAD_REQUEST, &format!("invalid request body: {}", err)),
+            }
+        }};
+    }
+    macro_rules! namespace {
+        ($app:expr, $cluster:expr, $ns:expr) => {{
+            let id = namespace_id($app, $cluster, $ns);
+            if !state.namespaces.contains_key(&id) {
+                return error_resp(StatusCode::NOT_FOUND, &format!("namespace not found for {}", $ns));
+            }
+            id
+        }};
+    }
+
+    match (method, segments) {
+        (Method::GET, ["apps"]) => {
+            let apps: BTreeSet<&String> = state.clusters.iter().map(|(app, _)| app).collect();
+            let apps: Vec<serde_json::Value> = apps.into_iter().map(|app| json!({
+                "name": app, "appId": app, "orgId": "TEST", "orgName": "test", "ownerName": "apollo", "ownerEmail": "apollo@example.com",
+                "dataChangeCreatedBy": "apollo", "dataChangeLastModifiedBy": "apollo", "dataChangeCreatedTime": "", "dataChangeLastModifiedTime": "",
+            })).collect();
+            json_resp(StatusCode::OK, serde_json::Value::Array(apps))
+        }
+        (Method::GET, ["apps", app_id, "envclusters"]) => {
+            let clusters: Vec<&String> = state.clusters.iter().filter(|(app, _)| app == app_id).map(|(_, c)| c).collect();
+            json_resp(StatusCode::OK, json!([{ "env": "DEV", "clusters": clusters }]))
+        }
+        (Method::POST, ["apps", app_id, "appnamespaces"]) => {
+            let req = body!(CreateNamespaceReq);
+            let clusters: Vec<String> = state.clusters.iter().filter(|(app, _)| app == app_id).map(|(_, c)| c.clone()).collect();
+            for cluster in clusters {
+                state.namespaces.entry(namespace_id(app_id, &cluster, &req.name)).or_default();
+            }
+            json_resp(StatusCode::OK, json!({
+                "name": req.name, "appId": app_id, "format": req.format, "isPublic": req.isPublic, "comment": req.comment,
+                "dataChangeCreatedBy": req.dataChangeCreatedBy, "dataChangeLastModifiedBy": req.dataChangeCreatedBy,
+                "dataChangeCreatedTime": "", "dataChangeLastModifiedTime": "",
+            }))
+        }
+        (Method::PUT, ["envs", _env, "releases", release_id, "rollback"]) => {
+            let release = release_id.parse::<u64>().ok().filter(|id| *id >= 1 && *id as usize <= state.releases.len());
+            if release.is_none() {
+                return error_resp(StatusCode::NOT_FOUND, &format!("release not found for {}", release_id));
+            }
+            let id = state.releases[release.unwrap() as usize - 1].namespace.clone();
+            let history = state.namespaces[&id].releases.clone();
+            let idx = history.iter().position(|r| *r == release.unwrap()).unwrap();
+            let configurations = if idx == 0 { HashMap::new() } else { state.releases[history[idx - 1] as usize - 1].configurations.clone() };
+            let operator = params.get("operator").cloned().unwrap_or_default();
+            state.apply_release(&id, configurations, "rollback", "", &operator);
+            shared.notify.notify_waiters();
+            empty_resp(StatusCode::OK)
+        }
+        (Method::GET, ["envs", _env, "apps", app_id, "clusters", cluster]) => {
+            if !state.clusters.contains(&(app_id.to_string(), cluster.to_string())) {
+                return error_resp(StatusCode::NOT_FOUND, &format!("cluster not found for {}", cluster));
+            }
+            json_resp(StatusCode::OK, cluster_info(app_id, cluster, "apollo"))
+        }
+        (Method::POST, ["envs", _env, "apps", app_id, "clusters"]) => {
+            let req = body!(CreateClusterReq);
+            if !state.clusters.insert((app_id.to_string(), req.name.clone())) {
+                return error_resp(StatusCode::BAD_REQUEST, &format!("cluster already exists: {}", req.name));
+            }
+            let namespaces: BTreeSet<String> = state.namespaces.keys().filter(|id| id.0 == *app_id).map(|id| id.2.clone()).collect();
+            for ns in namespaces {
+                state.namespaces.entry(namespace_id(app_id, &req.name, &ns)).or_default();
+            }
+            json_resp(StatusCode::OK, cluster_info(app_id, &req.name, &req.dataChangeCreatedBy))
+        }
+        (Method::GET, ["envs", _env, "apps", app_id, "clusters", cluster, "namespaces"]) => {
+            let ids: Vec<NamespaceId> = state.namespaces.keys().filter(|id| id.0 == *app_id && id.1 == *cluster).cloned().collect();
+            let details: Vec<serde_json::Value> = ids.iter().map(|id| state.namespace_detail(id)).collect();
+            json_resp(StatusCode::OK, serde_json::Value::Array(details))
+        }
+        (Method::GET, ["envs", _env, "apps", app_id, "clusters", cluster, "namespaces", ns]) => {
+            let id = namespace!(app_id, cluster, ns);
+            json_resp(StatusCode::OK, state.namespace_detail(&id))
+        }
+        (Method::GET, ["envs", _env, "apps", app_id, "clusters", cluster, "namespaces", ns, "lock"]) => {
+            let _ = namespace!(app_id, cluster, ns);
+            json_resp(StatusCode::OK, json!({ "namespaceName": ns, "isLocked": false }))
+        }
+        (Method::GET, ["envs", _env, "apps", app_id, "clusters", cluster, "namespaces", ns, "items", key]) => {
+            let id = namespace!(app_id, cluster, ns);
+            let value = state.namespaces[&id].items.get(*key);
+            if value.is_none() {
+                return error_resp(StatusCode::NOT_FOUND, &format!("item not found for {}", key));
+            }
+            json_resp(StatusCode::OK, config_item(key, value.unwrap()))
+        }
+        (Method::POST, ["envs", _env, "apps", app_id, "clusters", cluster, "namespaces", ns, "items"]) => {
+            let id = namespace!(app_id, cluster, ns);
+            let req = body!(CreateConfigItemReq);
+            let items = &mut state.namespaces.get_mut(&id).unwrap().items;
+            if items.contains_key(&req.key) {
+                return error_resp(StatusCode::BAD_REQUEST, &format!("item already exists: {}", req.key));
+            }
+            items.insert(req.key.clone(), req.value.clone());
+            json_resp(StatusCode::OK, config_item(&req.key, &req.value))
+        }
+        (Method::PUT, ["envs", _env, "apps", app_id, "clusters", cluster, "namespaces", ns, "items", key]) => {
+            let id = namespace!(app_id, cluster, ns);
+            let req = body!(UpdateConfigItemReq);
+            let items = &mut state.namespaces.get_mut(&id).unwrap().items;
+            let create = params.get("createIfNotExists").map(|v| v == "true").unwrap_or(false);
+            if !items.contains_key(*key) && !create {
+                return error_resp(StatusCode::NOT_FOUND, &format!("item not found for {}", key));
+            }
+            items.insert(key.to_string(), req.value);
+            empty_resp(StatusCode::OK)
+        }
+        (Method::DELETE, ["envs", _env, "apps", app_id, "clusters", cluster, "namespaces", ns, "items", key]) => {
+            let id = namespace!(app_id, cluster, ns);
+            if state.namespaces.get_mut(&id).unwrap().items.remove(*key).is_none() {
+                return error_resp(StatusCode::NOT_FOUND, &format!("item not found for {}", key));
+            }
+            empty_resp(StatusCode::OK)
+        }
+        (Method::POST, ["envs", _env, "apps", app_id, "clusters", cluster, "namespaces", ns, "releases"]) => {
+            let id = namespace!(app_id, cluster, ns);
+            let req = body!(ReleaseConfigReq);
+            let release_id = state.publish(&id, &req.releaseTitle, &req.releaseComment, &req.releasedBy);
+            shared.notify.notify_waiters();
+            json_resp(StatusCode::OK, state.release_resp(release_id))
+        }
+        (Method::GET, ["envs", _env, "apps", app_id, "clusters", cluster, "namespaces", ns, "releases", "latest"]) => {
+            let id = namespace!(app_id, cluster, ns);
+            let latest = state.namespaces[&id].releases.last().cloned();
+            if latest.is_none() {
+                return empty_resp(StatusCode::OK);
+            }
+            json_resp(StatusCode::OK, state.release_resp(latest.unwrap()))
+        }
+        _ => error_resp(StatusCode::NOT_FOUND, "openapi not found"),
+    }
+}
+
+fn cluster_info(app_id: &str, cluster: &str, created_by: &str) -> serde_json::Value {
+    json!({
+        "name": cluster, "appId": app_id,
+        "dataChangeCreatedBy": created_by, "dataChangeLastModifiedBy": created_by,
+        "dataChangeCreatedTime": "", "dataChangeLastModifiedTime": "",
+    })
+}
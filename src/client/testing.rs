@@ -0,0 +1,103 @@
+//! 测试辅助: 不依赖Apollo服务的内存版config client
+//!
+//! `FakeApolloConfig::client` 返回的就是 `ApolloConfigClient`, 读取配置, 占位符, 本地覆盖和变更事件的行为与真实client一致.
+//! 修改通过 `set` / `remove` 暂存, `publish` 时作为一次发布生效. 没有config server, `listen_namespace` 将namespace
+//! 作为未发布的空namespace监听(status中source为`NotFound`), 之后 `publish` 写入配置. 未监听的namespace首次 `publish` 时以优先级0被监听
+
+use std::{collections::HashMap, sync::Mutex};
+
+use tokio::sync::broadcast::{self, error::TryRecvError};
+
+use super::apollo_config_client::{self, ApolloChangeEvent, ApolloConfigClient};
+
+pub struct FakeApolloConfig {
+    client: ApolloConfigClient,
+    /// 已发布的配置
+    released: Mutex<HashMap<String, HashMap<String, String>>>,
+    /// 未发布的修改, value 为 None 表示删除
+    pending: Mutex<HashMap<String, HashMap<String, Option<String>>>>,
+    release_seq: Mutex<u64>,
+    /// 通过`subscribe`订阅, 与`fetch_change_event`的队列互不影响
+    events_rx: Mutex<broadcast::Receiver<Vec<ApolloChangeEvent>>>,
+    /// 已从订阅中取出, 还未被`take_events`返回的事件
+    events: Mutex<Vec<ApolloChangeEvent>>,
+    _close_rx: tokio::sync::watch::Receiver<bool>,
+}
+
+impl FakeApolloConfig {
+    pub fn new(app_id: &str, cluster_name: &str) -> FakeApolloConfig {
+        let (client, close_rx) = apollo_config_client::new_offline(app_id, cluster_name);
+        let events_rx = Mutex::new(client.subscribe());
+        FakeApolloConfig {
+            client,
+            released: Mutex::new(HashMap::new()),
+            pending: Mutex::new(HashMap::new()),
+            release_seq: Mutex::new(0),
+            events_rx,
+            events: Mutex::new(Vec::new()),
+            _close_rx: close_rx,
+        }
+    }
+
+    /// 传给被测代码使用的client
+    pub fn client(&self) -> &ApolloConfigClient {
+        &self.client
+    }
+
+    /// 暂存一个配置修改, `publish` 后生效
+    pub fn set(&self, namespace: &str, key: &str, value: &str) {
+        self.pending.lock().unwrap().entry(namespace.to_string()).or_default().insert(key.to_string(), Some(value.to_string()));
+    }
+
+    /// 暂存一个配置删除, `publish` 后生效
+    pub fn remove(&self, namespace: &str, key: &str) {
+        self.pending.lock().unwrap().entry(namespace.to_string()).or_default().insert(key.to_string(), None);
+    }
+
+    /// 发布namespace的暂存修改, 返回新的release key
+    pub fn publish(&self, namespace: &str) -> String {
+        let changes = self.pending.lock().unwrap().remove(namespace).unwrap_or_default();
+        let mut released = self.released.lock().unwrap();
+        let configurations = released.entry(namespace.to_string()).or_default();
+        for (key, value) in changes {
+            match value {
+                Some(value) => configurations.insert(key, value),
+                None => configurations.remove(&key),
+            };
+        }
+
+        let mut seq = self.release_seq.lock().unwrap();
+        *seq += 1;
+        let release_key = format!("fake-release-{}", seq);
+        //发布前后都取出订阅中的事件, 连续发布不会超出订阅的容量
+        self.drain_events();
+        self.client.apply_release(namespace, &release_key, configurations.clone());
+        self.drain_events();
+        release_key
+    }
+
+    /// 暂存并立即发布一个配置修改
+    pub fn set_and_publish(&self, namespace: &str, key: &str, value: &str) -> String {
+        self.set(namespace, key, value);
+        self.publish(namespace)
+    }
+
+    /// 取出上一次调用之后产生的所有变更事件. `publish`产生的事件不会丢失,
+    /// 其他来源(如本地覆盖文件)的事件在两次`publish`/`take_events`之间超出订阅容量时会丢失
+    pub fn take_events(&self) -> Vec<ApolloChangeEvent> {
+        self.drain_events();
+        std::mem::take(&mut *self.events.lock().unwrap())
+    }
+
+    fn drain_events(&self) {
+        let mut rx = self.events_rx.lock().unwrap();
+        let mut events = self.events.lock().unwrap();
+        loop {
+            match rx.try_recv() {
+                Ok(batch) => events.extend(batch),
+                Err(TryRecvError::Lagged(n)) => log::warn!("fake apollo config lost {} batches of change events", n),
+                Err(_) => return,
+            }
+        }
+    }
+}
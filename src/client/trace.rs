@@ -0,0 +1,20 @@
+//! tracing 支持, 未开启 `tracing` feature 时为空操作
+
+/// 在当前span上记录字段, 字段需要在span创建时声明
+macro_rules! record_field {
+    ($name:literal, $value:expr) => {
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record($name, tracing::field::display($value));
+    };
+}
+
+/// 输出结构化事件, 参数同 `tracing::event!`
+macro_rules! trace_event {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        tracing::event!($($arg)+);
+    };
+}
+
+pub(crate) use record_field;
+pub(crate) use trace_event;
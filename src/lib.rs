@@ -1,10 +1,17 @@
 
+// 让derive生成的 `::apollo_sdk::...` 路径在本crate内也能解析
+extern crate self as apollo_sdk;
+
 pub mod client;
 
+#[cfg(feature = "derive")]
+pub use apollo_sdk_derive::ApolloConfig;
+
 #[cfg(test)]
 mod tests {
     use std::time;
 
+    use crate::client::test_server::ApolloTestServer;
     use crate::client::apollo_openapi_client::{ApolloOpenApiClient, CreateClusterReq, CreateNamespaceReq, CreateConfigItemReq, UpdateConfigItemReq, ReleaseConfigReq};
 
     use super::*;
@@ -15,7 +22,6 @@ mod tests {
         };
     }
     
-    const META_SRV_ADDR: &str = "http://localhost:8080";
     const NS_NS1: &str = "application";
     const NS_NS2: &str = "ns2";
     const APP_ID: &str = "SampleApp";
@@ -23,13 +29,41 @@ mod tests {
     const SECERT: &str = "10b890f2d8b642c885f250ec19f1d0ac";
     const KEY: &str = "timeout";
 
+    /// 修改环境变量的测试串行执行, 避免与其他测试同时读写环境变量
+    static ENV_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
     const TOKEN: &str = "fea70126ea2f59d128f3d78db7c494e95fd980e6";     //SampleApp app 权限
-    const THIRD_PARTY_APP: &str = "demo";   
-    const PORTAL_URL: &str = "http://localhost:8070";
+
+    /// 本地启动的Apollo服务端, SampleApp 的 DEV 和 default cluster 下各有已发布的 application 和 ns2
+    fn apollo_server() -> ApolloTestServer {
+        let server = ApolloTestServer::start();
+        server.set_long_poll_timeout(time::Duration::from_secs(1));
+        server.add_portal_token(TOKEN);
+        for cluster in [CLUSTER, "default"] {
+            server.set(APP_ID, cluster, NS_NS1, KEY, "100");
+            server.set(APP_ID, cluster, NS_NS2, KEY, "9090");
+            server.publish(APP_ID, cluster, NS_NS1);
+            server.publish(APP_ID, cluster, NS_NS2);
+        }
+        server
+    }
+
+    /// 最多等待10秒接收一条消息
+    fn recv_timeout<T: Clone>(rx: &mut tokio::sync::broadcast::Receiver<T>) -> T {
+        for _ in 0..200 {
+            if let Ok(res) = rx.try_recv() {
+                return res;
+            }
+            std::thread::sleep(time::Duration::from_millis(50));
+        }
+        panic!("no message received in 10s");
+    }
 
     #[test]
     fn test_apollo_config_cli() {
-        let meta = vec![META_SRV_ADDR];
+        let server = apollo_server();
+        let meta_srv_addr = server.url();
+        let meta = vec![meta_srv_addr.as_str()];
         
         //未设置 secret 
         let conn_future = client::apollo_config_client::new(meta, APP_ID, CLUSTER, None, None);
@@ -58,9 +92,21 @@ mod tests {
         assert!(value.is_some());
         assert_eq!(value.unwrap().config_value, "100");
 
-        std::thread::sleep(time::Duration::from_secs(20));
-        let value = apc.get_config("testKey");
+        //发布后通过长轮询收到变更
+        server.set(APP_ID, CLUSTER, NS_NS1, "testKey", "testValue");
+        server.publish(APP_ID, CLUSTER, NS_NS1);
+        let mut value = None;
+        for _ in 0..100 {
+            value = apc.get_config("testKey");
+            if value.is_some() {
+                break;
+            }
+            std::thread::sleep(time::Duration::from_millis(100));
+        }
         assert_eq!(value.unwrap().config_value, "testValue");
+        let events = apc.fetch_change_event();
+        assert!(events.is_some());
+        assert_eq!(events.unwrap()[0].key, "testKey");
 
         apc.close();
 
@@ -68,10 +114,16 @@ mod tests {
 
     #[test]
     fn test_init_with_ns() {
-        let meta = vec![META_SRV_ADDR];
-        let ns = Some(vec![NS_NS1, NS_NS2]);
+        let server = apollo_server();
+        server.set_secret(APP_ID, SECERT);
+        let meta_srv_addr = server.url();
         
-        let conn_future = client::apollo_config_client::new(meta, APP_ID, CLUSTER, ns, Some(SECERT));
+        let conn_future = client::apollo_config_client::new(vec![&meta_srv_addr], APP_ID, CLUSTER, Some(vec![NS_NS1]), None);
+        let conn_res = async_test!(conn_future);
+        assert!(matches!(conn_res.err(), Some(client::error::ApolloError::Unauthorized { status: 401, .. })));
+
+        let ns = Some(vec![NS_NS1, NS_NS2]);
+        let conn_future = client::apollo_config_client::new(vec![&meta_srv_addr], APP_ID, CLUSTER, ns, Some(SECERT));
 
         let conn_res = async_test!(conn_future);
         assert!(conn_res.is_ok());
@@ -85,6 +137,10 @@ mod tests {
         assert!(value.is_some());
         assert_eq!(value.unwrap().config_value, "100");
 
+        let res = apc.listen_namespace("notExists");
+        let res = async_test!(res);
+        assert!(matches!(res, Some(client::error::ApolloError::NamespaceNotFound { .. })));
+        apc.close();
     }
 
     #[test]
@@ -94,35 +150,43 @@ mod tests {
 
     #[test]
     fn get_env_and_cluster() {
-        let api_cli = ApolloOpenApiClient::new(PORTAL_URL, TOKEN);
+        let server = apollo_server();
+        let api_cli = ApolloOpenApiClient::new(&server.url(), TOKEN);
         let app = "SampleApp";
         let res = api_cli.get_app_env_clusters(app);
         let res = async_test!(res);
-        println!("{:?}", res);
+        assert!(res.unwrap()[0].clusters.contains(&"default".to_string()));
     }
 
     #[test]
     fn get_apps() {
-        let api_cli = ApolloOpenApiClient::new(PORTAL_URL, TOKEN);
+        let server = apollo_server();
+        let api_cli = ApolloOpenApiClient::new(&server.url(), TOKEN);
         let res = api_cli.get_apps();
         let res = async_test!(res);
-        println!("{:?}", res);
+        assert!(res.unwrap().iter().any(|app| app.appId == APP_ID));
+
+        let api_cli = ApolloOpenApiClient::new(&server.url(), "invalid token");
+        let res = async_test!(api_cli.get_apps());
+        assert!(matches!(res.err(), Some(client::error::ApolloError::Unauthorized { status: 401, .. })));
     }
 
     #[test]
     fn get_cluster() {
-        let api_cli = ApolloOpenApiClient::new(PORTAL_URL, TOKEN);
+        let server = apollo_server();
+        let api_cli = ApolloOpenApiClient::new(&server.url(), TOKEN);
         let app = "SampleApp";
         let env = "DEV";
         let cluster = "default";
         let res = api_cli.get_cluster(env, app, cluster);
         let res = async_test!(res);
-        println!("{:?}", res);
+        assert_eq!(res.unwrap().name, cluster);
     }
 
     #[test]
     fn create_cluster() {
-        let api_cli = ApolloOpenApiClient::new(PORTAL_URL, TOKEN);
+        let server = apollo_server();
+        let api_cli = ApolloOpenApiClient::new(&server.url(), TOKEN);
         let env = "DEV";
         let req = CreateClusterReq{
             name: "oapi".to_string(),
@@ -131,24 +195,29 @@ mod tests {
         };
         let res = api_cli.create_cluster(env, &req);
         let res = async_test!(res);
-        println!("{:?}", res);
+        assert_eq!(res.unwrap().name, "oapi");
+
+        let res = api_cli.create_cluster(env, &req);
+        let res = async_test!(res);
+        assert!(matches!(res.err(), Some(client::error::ApolloError::HttpStatus { status: 400, .. })));
     }
 
     #[test]
     fn get_all_namespaces() {
-        let api_cli = ApolloOpenApiClient::new(PORTAL_URL, TOKEN);
+        let server = apollo_server();
+        let api_cli = ApolloOpenApiClient::new(&server.url(), TOKEN);
         let app = "SampleApp";
         let env = "DEV";
         let cluster = "default";
-        let namespace = "application";
         let res = api_cli.get_all_namespaces(env, app, cluster);
         let res = async_test!(res);
-        println!("{:?}", res);
+        assert_eq!(res.unwrap().len(), 2);
     }
 
     #[test]
     fn get_ns_detail() {
-        let api_cli = ApolloOpenApiClient::new(PORTAL_URL, TOKEN);
+        let server = apollo_server();
+        let api_cli = ApolloOpenApiClient::new(&server.url(), TOKEN);
         let app = "SampleApp";
         let env = "DEV";
         let cluster = "default";
@@ -156,16 +225,16 @@ mod tests {
 
         let res = api_cli.get_namespace_detail(env, app, cluster, namespace);
         let res = async_test!(res);
-        println!("{:?}", res);
+        let res = res.unwrap();
+        assert_eq!(res.namespaceName, namespace);
+        assert!(res.items.iter().any(|item| item.key == KEY && item.value == "100"));
     }
 
     #[test]
     fn create_namespace() {
-        let api_cli = ApolloOpenApiClient::new(PORTAL_URL, TOKEN);
+        let server = apollo_server();
+        let api_cli = ApolloOpenApiClient::new(&server.url(), TOKEN);
         let app = "SampleApp";
-        let env = "DEV";
-        let cluster = "default";
-        let namespace = "application";
 
         let req = CreateNamespaceReq {
             name: "newNs".to_string(),
@@ -177,37 +246,43 @@ mod tests {
         };
         let res = api_cli.create_namespace(&req);
         let res = async_test!(res);
-        println!("{:?}", res);
+        assert_eq!(res.unwrap().name, "newNs");
 
+        let res = api_cli.get_namespace_detail("DEV", app, "default", "newNs");
+        let res = async_test!(res);
+        assert!(res.is_ok());
     }
 
     #[test]
     fn get_current_editor() {
-        let api_cli = ApolloOpenApiClient::new(PORTAL_URL, TOKEN);
+        let server = apollo_server();
+        let api_cli = ApolloOpenApiClient::new(&server.url(), TOKEN);
         let app = "SampleApp";
         let env = "DEV";
         let cluster = "default";
         let namespace = "application";
         let res = api_cli.get_current_editor(env, app, cluster, namespace);
         let res = async_test!(res);
-        println!("{:?}", res);
+        assert!(!res.unwrap().isLocked);
     }
 
     #[test]
     fn get_config() {
-        let api_cli = ApolloOpenApiClient::new(PORTAL_URL, TOKEN);
+        let server = apollo_server();
+        let api_cli = ApolloOpenApiClient::new(&server.url(), TOKEN);
         let app = "SampleApp";
         let env = "DEV";
         let cluster = "default";
         let namespace = "application";
         let res = api_cli.get_config(env, app, cluster, namespace, "timeout");
         let res = async_test!(res);
-        println!("{:?}", res);
+        assert_eq!(res.unwrap().value, "100");
     }
 
     #[test]
     fn create_config() {
-        let api_cli = ApolloOpenApiClient::new(PORTAL_URL, TOKEN);
+        let server = apollo_server();
+        let api_cli = ApolloOpenApiClient::new(&server.url(), TOKEN);
         let app = "SampleApp";
         let env = "DEV";
         let cluster = "default";
@@ -221,12 +296,17 @@ mod tests {
         };
         let res = api_cli.create_config(env, app, cluster, namespace, &req);
         let res = async_test!(res);
-        println!("{:?}", res);
+        assert_eq!(res.unwrap().value, "create.new.value");
+
+        let res = api_cli.get_config(env, app, cluster, namespace, "create.new.key11");
+        let res = async_test!(res);
+        assert_eq!(res.unwrap().value, "create.new.value");
     }
 
     #[test]
     fn update_config() {
-        let api_cli = ApolloOpenApiClient::new(PORTAL_URL, TOKEN);
+        let server = apollo_server();
+        let api_cli = ApolloOpenApiClient::new(&server.url(), TOKEN);
         let app = "SampleApp";
         let env = "DEV";
         let cluster = "default";
@@ -241,29 +321,39 @@ mod tests {
         };
         let res = api_cli.update_config(env, app, cluster, namespace, &req);
         let res = async_test!(res);
-        println!("{:?}", res);
+        assert!(res.is_ok());
 
+        let res = api_cli.get_config(env, app, cluster, namespace, "create.new.key");
+        let res = async_test!(res);
+        assert_eq!(res.unwrap().value, "create.new.value---2s");
     }
 
     #[test]
     fn delete_config() {
-        let api_cli = ApolloOpenApiClient::new(PORTAL_URL, TOKEN);
+        let server = apollo_server();
+        let api_cli = ApolloOpenApiClient::new(&server.url(), TOKEN);
         let app = "SampleApp";
         let env = "DEV";
         let cluster = "default";
         let namespace = "application";
-        let res = api_cli.delete_config(env, app, cluster, namespace, "create.new.key", "apollo");
+        let res = api_cli.delete_config(env, app, cluster, namespace, KEY, "apollo");
+        let res = async_test!(res);
+        assert!(res.is_ok());
+
+        let res = api_cli.delete_config(env, app, cluster, namespace, KEY, "apollo");
         let res = async_test!(res);
-        println!("{:?}", res);
+        assert!(matches!(res.err(), Some(client::error::ApolloError::HttpStatus { status: 404, .. })));
     }
 
     #[test]
     fn release_config() {
-        let api_cli = ApolloOpenApiClient::new(PORTAL_URL, TOKEN);
+        let server = apollo_server();
+        let api_cli = ApolloOpenApiClient::new(&server.url(), TOKEN);
         let app = "SampleApp";
         let env = "DEV";
         let cluster = "default";
         let namespace = "application";
+        server.set(app, cluster, namespace, KEY, "200");
         let req = ReleaseConfigReq{
             releaseTitle: "release title".to_string(),
             releaseComment: "commentxx".to_string(),
@@ -271,19 +361,1069 @@ mod tests {
         };
         let res = api_cli.release_config(env, app, cluster, namespace, &req);
         let res = async_test!(res);
-        println!("{:?}", res);
+        let res = res.unwrap();
+        assert_eq!(res.name, "release title");
+        assert_eq!(res.configurations[KEY], "200");
+
+        //回滚到上一次发布
+        let release_id = server.latest_release_id(app, cluster, namespace).unwrap().to_string();
+        let res = api_cli.rollback_release(env, &release_id, "apollo");
+        let res = async_test!(res);
+        assert!(res.is_ok());
+        let res = async_test!(api_cli.get_namespace_latest_release(env, app, cluster, namespace));
+        assert_eq!(res.unwrap().configurations[KEY], "100");
     }
 
     #[test]
     fn get_namespace_latest_release() {
-        let api_cli = ApolloOpenApiClient::new(PORTAL_URL, TOKEN);
+        let server = apollo_server();
+        let api_cli = ApolloOpenApiClient::new(&server.url(), TOKEN);
         let app = "SampleApp";
         let env = "DEV";
         let cluster = "default";
         let namespace = "application";
         let res = api_cli.get_namespace_latest_release(env, app, cluster, namespace);
         let res = async_test!(res);
-        println!("{:?}", res);
+        assert_eq!(res.unwrap().configurations[KEY], "100");
+    }
+
+    #[test]
+    fn resolve_placeholder() {
+        use crate::client::placeholder::apollo_resolve_placeholder;
+        use std::collections::HashMap;
+
+        let mut cfg = HashMap::new();
+        cfg.insert("db.host", "127.0.0.1");
+        cfg.insert("db.port", "${db.port.default:3306}");
+        cfg.insert("url", "jdbc://${db.host}:${db.port}/${db.name:test}");
+        cfg.insert("loop.a", "${loop.b}");
+        cfg.insert("loop.b", "x${loop.a}");
+        let lookup = |key: &str| cfg.get(key).map(|v| v.to_string());
+
+        assert_eq!(apollo_resolve_placeholder("${url}", &lookup), "jdbc://127.0.0.1:3306/test");
+        assert_eq!(apollo_resolve_placeholder("${missing}", &lookup), "${missing}");
+        assert_eq!(apollo_resolve_placeholder("${missing:${db.host}}", &lookup), "127.0.0.1");
+        assert_eq!(apollo_resolve_placeholder("${db.host", &lookup), "${db.host");
+        assert_eq!(apollo_resolve_placeholder("${loop.a}", &lookup), "x${loop.a}");
+
+        let _env = ENV_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        std::env::set_var("APOLLO_SDK_TEST_PLACEHOLDER", "from_env");
+        assert_eq!(apollo_resolve_placeholder("${apollo.sdk.test.placeholder:default}", &lookup), "from_env");
+    }
+
+    #[test]
+    fn parse_override_file() {
+        use crate::client::layer::{parse_properties, env_override};
+
+        let content = "# comment\n! comment\n\ndb.host = 10.0.0.1\ndb.port:3306\nurl=jdbc://a:b\nflag\n";
+        let cfg = parse_properties(content);
+        assert_eq!(cfg.len(), 4);
+        assert_eq!(cfg.get("db.host").unwrap(), "10.0.0.1");
+        assert_eq!(cfg.get("db.port").unwrap(), "3306");
+        assert_eq!(cfg.get("url").unwrap(), "jdbc://a:b");
+        assert_eq!(cfg.get("flag").unwrap(), "");
+
+        let _env = ENV_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        std::env::set_var("APOLLO_SDK_TEST_DB_HOST", "10.0.0.2");
+        let env = env_override("APOLLO_SDK_TEST_", "db.host");
+        assert_eq!(env, Some(("APOLLO_SDK_TEST_DB_HOST".to_string(), "10.0.0.2".to_string())));
+        assert!(env_override("APOLLO_SDK_TEST_", "db.port").is_none());
+    }
+
+    #[test]
+    fn apollo_error_kind() {
+        use crate::client::error::ApolloError;
+        use std::error::Error;
+
+        let err = ApolloError::HttpStatus { status: 503, message: "unavailable".to_string() };
+        assert!(err.is_retryable());
+        assert_eq!(err.to_string(), "apollo server responded 503: unavailable");
+
+        let err = ApolloError::NamespaceNotFound { namespace: "ns404".to_string() };
+        assert!(!err.is_retryable());
+        assert!(err.source().is_none());
+
+        let err: ApolloError = serde_json::from_str::<Vec<String>>("{").unwrap_err().into();
+        assert!(matches!(err, ApolloError::Deserialize(_)));
+        assert!(!err.is_retryable());
+        assert!(err.source().is_some());
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn metrics_recorder() {
+        use crate::client::metrics::{self, MetricsRecorder, Labels};
+        use std::sync::{Arc, Mutex};
+
+        struct TestRecorder(Arc<Mutex<Vec<String>>>);
+        impl MetricsRecorder for TestRecorder {
+            fn increment_counter(&self, name: &'static str, labels: Labels, value: u64) {
+                self.0.lock().unwrap().push(format!("{}{:?}={}", name, labels, value));
+            }
+        }
+
+        let records = Arc::new(Mutex::new(Vec::new()));
+        assert!(metrics::set_metrics_recorder(Box::new(TestRecorder(records.clone()))));
+        assert!(!metrics::set_metrics_recorder(Box::new(TestRecorder(records.clone()))));
+
+        //其他测试并行运行时也会写入全局recorder, 只检查本测试产生的记录
+        metrics::timed("apollo_long_poll_total", "apollo_long_poll_duration_seconds", &[("outcome", "metrics_recorder_test")], std::time::Duration::from_millis(5));
+        let records: Vec<String> = records.lock().unwrap().iter().filter(|r| r.contains("metrics_recorder_test")).cloned().collect();
+        assert_eq!(records, vec!["apollo_long_poll_total[(\"outcome\", \"metrics_recorder_test\")]=1".to_string()]);
     }
 
+    #[test]
+    fn hmac_sha1_signer() {
+        use crate::client::signer::{hmac_sha1_signature, HmacSha1Signer, RequestSigner};
+
+        assert!(HmacSha1Signer.sign("", APP_ID, "/configs/x").is_empty());
+
+        //与Apollo Java客户端 SignatureTest 的结果一致
+        let path = "/configs/100004458/default/application?ip=10.0.0.1";
+        let signature = hmac_sha1_signature("df23df3f59884980844ff3dada30fa97", "1576478257344", path);
+        assert_eq!(signature, "EoKyziXvKqzHgwx+ijDJwgVTDgE=");
+
+        let headers = HmacSha1Signer.sign("df23df3f59884980844ff3dada30fa97", "100004458", path);
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers[0].0, "Timestamp");
+        assert_eq!(headers[1].0, "Authorization");
+        let expected = hmac_sha1_signature("df23df3f59884980844ff3dada30fa97", &headers[0].1, path);
+        assert_eq!(headers[1].1, format!("Apollo 100004458:{}", expected));
+    }
+
+    #[test]
+    fn access_key_rotation() {
+        use crate::client::signer::AccessKeySource;
+        use std::sync::{Arc, Mutex};
+
+        let server = apollo_server();
+        server.set_secret(APP_ID, "right");
+        let meta_srv_addr = server.url();
+        let apc = async_test!(client::apollo_config_client::new(vec![&meta_srv_addr], APP_ID, CLUSTER, None, Some("wrong"))).unwrap();
+        let res = async_test!(apc.listen_namespace(NS_NS1));
+        assert!(matches!(res, Some(client::error::ApolloError::Unauthorized { status: 401, .. })));
+
+        //401时换下一个secret重试
+        apc.set_access_keys(AccessKeySource::Static(vec!["wrong".to_string(), "right".to_string()]));
+        assert!(async_test!(apc.listen_namespace(NS_NS1)).is_none());
+        assert_eq!(apc.get_config(KEY).unwrap().config_value, "100");
+
+        //修改key文件后使用新的secret
+        let path = std::env::temp_dir().join(format!("apollo-access-keys-{}", std::process::id()));
+        std::fs::write(&path, "# current\nright\n").unwrap();
+        apc.set_access_keys(AccessKeySource::File(path.to_str().unwrap().to_string()));
+        assert!(async_test!(apc.listen_namespace(NS_NS2)).is_none());
+        server.set_secret(APP_ID, "rotated");
+        std::fs::write(&path, "rotated\n").unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(time::SystemTime::now() + time::Duration::from_secs(1)).unwrap();
+        assert!(apc.unlisten_namespace(NS_NS2));
+        assert!(async_test!(apc.listen_namespace(NS_NS2)).is_none());
+        let _ = std::fs::remove_file(&path);
+
+        //每次请求前从callback取secret
+        let secrets = Arc::new(Mutex::new(vec!["rotated".to_string()]));
+        let secrets_2 = secrets.clone();
+        apc.set_access_keys(AccessKeySource::Callback(Arc::new(move || secrets_2.lock().unwrap().clone())));
+        assert!(apc.unlisten_namespace(NS_NS2));
+        assert!(async_test!(apc.listen_namespace(NS_NS2)).is_none());
+        server.set_secret(APP_ID, "callback");
+        assert!(apc.unlisten_namespace(NS_NS2));
+        assert!(async_test!(apc.listen_namespace(NS_NS2)).is_some());
+        *secrets.lock().unwrap() = vec!["callback".to_string()];
+        assert!(async_test!(apc.listen_namespace(NS_NS2)).is_none());
+        apc.close();
+    }
+
+    #[cfg(feature = "blocking")]
+    #[test]
+    fn blocking_client_without_server() {
+        let res = client::blocking::new(vec!["http://127.0.0.1:1"], APP_ID, CLUSTER, None, None);
+        assert!(res.is_err());
+        assert!(res.err().unwrap().is_retryable());
+    }
+
+    #[test]
+    fn fake_config_client() {
+        use crate::client::{testing::FakeApolloConfig, apollo_config_client::ApolloChangeAction};
+
+        let fake = FakeApolloConfig::new(APP_ID, CLUSTER);
+        let acc = fake.client();
+        assert!(acc.get_config(KEY).is_none());
+
+        fake.set(NS_NS1, KEY, "100");
+        fake.set(NS_NS1, "url", "http://${host}");
+        assert!(acc.get_config(KEY).is_none());
+        let rk = fake.publish(NS_NS1);
+        assert_eq!(acc.get_config(KEY).unwrap().config_value, "100");
+        assert_eq!(acc.status().namespaces[0].release_key, rk);
+        assert_eq!(fake.take_events().len(), 2);
+
+        fake.set_and_publish(NS_NS2, KEY, "200");
+        let item = acc.get_config(KEY).unwrap();
+        assert_eq!((item.config_value.as_str(), item.namespace.as_str()), ("200", NS_NS2));
+        let events = fake.take_events();
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0].action, ApolloChangeAction::UPDATE));
+
+        acc.enable_placeholder(true);
+        fake.take_events();
+        fake.set_and_publish(NS_NS2, "host", "example.com");
+        assert_eq!(acc.get_config("url").unwrap().config_value, "http://example.com");
+
+        fake.remove(NS_NS2, KEY);
+        fake.publish(NS_NS2);
+        assert_eq!(acc.get_config(KEY).unwrap().config_value, "100");
+        assert!(fake.take_events().iter().any(|e| e.key == KEY && e.new_value == "100"));
+
+        acc.close();
+        assert!(acc.get_config(KEY).is_none());
+    }
+
+    #[test]
+    fn composite_config_source() {
+        use crate::client::source::{ConfigSource, StaticSource, PropertiesFileSource, CompositeSource};
+        use crate::client::apollo_config_client::ApolloConfigLayer;
+        use std::{collections::HashMap, sync::Arc};
+
+        let path = std::env::temp_dir().join(format!("apollo-source-{}.properties", std::process::id()));
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "timeout=200\nfile.only=yes\n").unwrap();
+
+        let defaults = Arc::new(StaticSource::new("defaults", HashMap::from([
+            (KEY.to_string(), "100".to_string()),
+            ("retries".to_string(), "3".to_string()),
+        ])));
+        let file = Arc::new(PropertiesFileSource::open(path).unwrap());
+        assert!(PropertiesFileSource::open("/not/exists.properties").is_err());
+
+        let composite = CompositeSource::new(vec![defaults.clone(), file.clone()]);
+        let item = composite.get(KEY).unwrap();
+        assert_eq!((item.config_value.as_str(), item.layer), ("200", ApolloConfigLayer::File));
+        assert_eq!(composite.get("retries").unwrap().layer, ApolloConfigLayer::Static);
+        assert_eq!(composite.keys(), vec!["file.only", "retries", KEY]);
+
+        //被更高优先级来源覆盖的key变化不产生事件
+        let mut rx = composite.subscribe();
+        defaults.replace(HashMap::from([
+            (KEY.to_string(), "150".to_string()),
+            ("retries".to_string(), "5".to_string()),
+        ]));
+        let mut events = None;
+        for _ in 0..50 {
+            if let Ok(res) = rx.try_recv() {
+                events = Some(res);
+                break;
+            }
+            std::thread::sleep(time::Duration::from_millis(20));
+        }
+        let events = events.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].key.as_str(), events[0].new_value.as_str()), ("retries", "5"));
+
+        std::fs::remove_file(path).unwrap();
+        assert!(file.get("file.only").is_none());
+        assert_eq!(composite.get(KEY).unwrap().config_value, "150");
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn config_crate_source() {
+        use crate::client::{testing::FakeApolloConfig, config_rs::{ApolloSource, ConfigReloader}};
+
+        let fake = FakeApolloConfig::new(APP_ID, CLUSTER);
+        fake.set(NS_NS1, "db.host", "localhost");
+        fake.set(NS_NS1, "db.port", "3306");
+        fake.publish(NS_NS1);
+        fake.set_and_publish(NS_NS2, "db.port", "3307");
+        fake.client().set_namespace_priority(NS_NS1, 10);
+
+        let client = fake.client().clone();
+        let cfg = config::Config::builder().add_source(ApolloSource::new(client.clone())).build().unwrap();
+        assert_eq!(cfg.get::<u16>("db.port").unwrap(), 3306);
+        let cfg = config::Config::builder().add_source(ApolloSource::with_namespaces(client.clone(), &[NS_NS2])).build().unwrap();
+        assert_eq!(cfg.get::<u16>("db.port").unwrap(), 3307);
+        assert!(cfg.get::<String>("db.host").is_err());
+
+        let source = ApolloSource::with_namespaces(client.clone(), &[NS_NS1, NS_NS2]);
+        let reloader = ConfigReloader::watch(&client, move || config::Config::builder().add_source(source.clone()).build()).unwrap();
+        assert_eq!(reloader.config().get::<String>("db.host").unwrap(), "localhost");
+
+        fake.set_and_publish(NS_NS1, "db.host", "10.0.0.1");
+        let mut host = String::new();
+        for _ in 0..50 {
+            host = reloader.config().get::<String>("db.host").unwrap();
+            if host != "localhost" {
+                break;
+            }
+            std::thread::sleep(time::Duration::from_millis(20));
+        }
+        assert_eq!(host, "10.0.0.1");
+
+        //只使用被遮盖的namespace时, 其变更不改变生效值, 仍会重新构建
+        let source = ApolloSource::with_namespaces(client.clone(), &[NS_NS2]);
+        let reloader = ConfigReloader::watch(&client, move || config::Config::builder().add_source(source.clone()).build()).unwrap();
+        fake.set_and_publish(NS_NS2, "db.port", "3308");
+        let mut port = 0;
+        for _ in 0..50 {
+            port = reloader.config().get::<u16>("db.port").unwrap();
+            if port != 3307 {
+                break;
+            }
+            std::thread::sleep(time::Duration::from_millis(20));
+        }
+        assert_eq!(port, 3308);
+        assert_eq!(client.get_config("db.port").unwrap().config_value, "3306");
+    }
+
+    #[cfg(feature = "figment")]
+    #[test]
+    fn figment_provider() {
+        use crate::client::{testing::FakeApolloConfig, figment::{ApolloProvider, FigmentReloader, merge_namespaces}};
+        use serde::Deserialize;
+
+        #[derive(Deserialize)]
+        struct Db {
+            host: String,
+            port: u16,
+        }
+
+        let fake = FakeApolloConfig::new(APP_ID, CLUSTER);
+        fake.set(NS_NS1, "db.host", "localhost");
+        fake.set(NS_NS1, "db.port", "3306");
+        fake.publish(NS_NS1);
+        fake.set_and_publish(NS_NS2, "db.port", "3307");
+        let client = fake.client().clone();
+
+        let db: Db = figment::Figment::from(ApolloProvider::new(client.clone())).extract_inner("db").unwrap();
+        assert_eq!((db.host.as_str(), db.port), ("localhost", 3307));
+
+        let fig = merge_namespaces(figment::Figment::new(), &client, &[(NS_NS2, "prod")]);
+        let db: Db = fig.extract_inner("db").unwrap();
+        assert_eq!(db.port, 3306);
+        let fig = fig.select("prod");
+        let db: Db = fig.extract_inner("db").unwrap();
+        assert_eq!(db.port, 3307);
+        assert_eq!(fig.find_metadata("db.port").unwrap().name, "Apollo namespace `ns2`");
+        assert_eq!(fig.find_metadata("db.host").unwrap().name, "Apollo namespace `application`");
+
+        //默认profile中的port被ns2遮盖, 变更时仍会重新merge
+        let client_2 = client.clone();
+        let reloader = FigmentReloader::watch(&client, move || merge_namespaces(figment::Figment::new(), &client_2, &[(NS_NS2, "prod")]));
+        fake.set_and_publish(NS_NS1, "db.port", "3316");
+        let mut port = 0;
+        for _ in 0..50 {
+            port = reloader.figment().extract_inner::<Db>("db").unwrap().port;
+            if port != 3306 {
+                break;
+            }
+            std::thread::sleep(time::Duration::from_millis(20));
+        }
+        assert_eq!(port, 3316);
+        assert_eq!(client.get_config("db.port").unwrap().config_value, "3307");
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derive_apollo_config() {
+        use crate::client::{testing::FakeApolloConfig, declare::{ApolloConfig, Reloadable}, error::ApolloError};
+
+        fn check_pool(size: &u32) -> Result<(), String> {
+            if *size == 0 { Err("must be positive".to_string()) } else { Ok(()) }
+        }
+        fn check_db(db: &DbConfig) -> Result<(), String> {
+            if db.url.starts_with("mysql://") { Ok(()) } else { Err(format!("unsupported url {}", db.url)) }
+        }
+
+        #[derive(crate::ApolloConfig, Debug)]
+        #[apollo(namespace = "application", validate = "check_db")]
+        struct DbConfig {
+            #[apollo(namespace = "ns2", key = "pool.size", default = 10, validate = "check_pool")]
+            pool_size: u32,
+            #[apollo(key = "db.url")]
+            url: String,
+            timeout: Option<u64>,
+        }
+
+        let fake = FakeApolloConfig::new(APP_ID, CLUSTER);
+        assert!(matches!(DbConfig::load(fake.client()), Err(ApolloError::InvalidConfig { key: Some(k), .. }) if k == "db.url"));
+
+        fake.set_and_publish(NS_NS1, "db.url", "mysql://localhost");
+        let db = DbConfig::load(fake.client()).unwrap();
+        assert_eq!((db.pool_size, db.timeout), (10, None));
+        assert_eq!(DbConfig::keys(), vec![(Some(NS_NS2), "pool.size"), (Some(NS_NS1), "db.url"), (Some(NS_NS1), "timeout")]);
+
+        fake.set_and_publish(NS_NS2, "pool.size", "0");
+        assert!(matches!(DbConfig::load(fake.client()), Err(ApolloError::InvalidConfig { key: Some(k), .. }) if k == "pool.size"));
+        fake.set_and_publish(NS_NS2, "pool.size", "x");
+        assert!(DbConfig::load(fake.client()).is_err());
+        fake.set_and_publish(NS_NS2, "pool.size", "20");
+
+        let db = Reloadable::<DbConfig>::watch(fake.client()).unwrap();
+        assert_eq!(db.get().pool_size, 20);
+        fake.set_and_publish(NS_NS1, "db.url", "postgres://localhost");
+        fake.set_and_publish(NS_NS1, "timeout", "30");
+        let mut timeout = None;
+        for _ in 0..50 {
+            timeout = db.get().timeout;
+            if timeout.is_some() {
+                break;
+            }
+            std::thread::sleep(time::Duration::from_millis(20));
+        }
+        //校验失败的那次发布被忽略
+        assert_eq!(timeout, None);
+        assert_eq!(db.get().url, "mysql://localhost");
+        fake.set_and_publish(NS_NS1, "db.url", "mysql://10.0.0.1");
+        for _ in 0..50 {
+            if db.get().timeout.is_some() {
+                break;
+            }
+            std::thread::sleep(time::Duration::from_millis(20));
+        }
+        assert_eq!((db.get().timeout, db.get().url.as_str()), (Some(30), "mysql://10.0.0.1"));
+
+        //db.url被ns2遮盖, application中的变更不改变生效值, 仍会重新加载
+        fake.set_and_publish(NS_NS2, "db.url", "mysql://shadow");
+        fake.set_and_publish(NS_NS1, "db.url", "mysql://10.0.0.2");
+        assert_eq!(fake.client().get_config("db.url").unwrap().config_value, "mysql://shadow");
+        for _ in 0..50 {
+            if db.get().url != "mysql://10.0.0.1" {
+                break;
+            }
+            std::thread::sleep(time::Duration::from_millis(20));
+        }
+        assert_eq!(db.get().url, "mysql://10.0.0.2");
+    }
+
+    #[test]
+    fn access_report() {
+        use crate::client::testing::FakeApolloConfig;
+
+        let fake = FakeApolloConfig::new(APP_ID, CLUSTER);
+        fake.set(NS_NS1, KEY, "100");
+        fake.set(NS_NS1, "unused.key", "x");
+        fake.publish(NS_NS1);
+        fake.set_and_publish(NS_NS2, "ns2.key", "y");
+        let acc = fake.client();
+
+        acc.get_config(KEY);
+        assert!(acc.access_report().accessed.is_empty());
+
+        acc.enable_access_tracking(true);
+        acc.get_config(KEY);
+        acc.get_config(KEY);
+        acc.get_config("not.exists");
+        acc.get_config_from_namespace("ns2.key", NS_NS2);
+        acc.get_config_from_namespace(KEY, NS_NS2);
+
+        let report = acc.access_report();
+        assert_eq!(report.accessed.len(), 4);
+        let timeout = report.accessed.iter().find(|a| a.namespace.is_none() && a.key == KEY).unwrap();
+        assert_eq!((timeout.hits, timeout.misses), (2, 0));
+        assert_eq!(report.unused, vec![(NS_NS1.to_string(), "unused.key".to_string())]);
+        let missing: Vec<(Option<&str>, &str)> = report.missing.iter().map(|a| (a.namespace.as_deref(), a.key.as_str())).collect();
+        assert_eq!(missing, vec![(None, "not.exists"), (Some(NS_NS2), KEY)]);
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["unused"][0][1], "unused.key");
+
+        acc.enable_access_tracking(false);
+        assert!(acc.access_report().accessed.is_empty());
+    }
+
+    #[test]
+    fn optional_namespace_and_wait_for() {
+        let server = apollo_server();
+        let meta_srv_addr = server.url();
+        let conn_future = client::apollo_config_client::new_with_optional_namespaces(vec![&meta_srv_addr], APP_ID, CLUSTER, vec![NS_NS1], vec!["later"], None);
+        let apc = async_test!(conn_future).unwrap();
+        let status = apc.status();
+        let later = status.namespaces.iter().find(|ns| ns.namespace == "later").unwrap();
+        assert_eq!(later.source, client::apollo_config_client::ApolloNamespaceSource::NotFound);
+        assert!(later.last_error.is_some());
+
+        let res = async_test!(apc.wait_for(&[KEY, "lateKey"], time::Duration::from_millis(200)));
+        assert!(matches!(res, Err(client::error::ApolloError::Timeout)));
+
+        //可选namespace发布后通过长轮询拉取
+        server.set(APP_ID, CLUSTER, "later", "lateKey", "ready");
+        server.publish(APP_ID, CLUSTER, "later");
+        let res = async_test!(apc.wait_for(&[KEY, "lateKey"], time::Duration::from_secs(10)));
+        assert!(res.is_ok());
+        assert_eq!(apc.get_config_from_namespace("lateKey", "later").unwrap().config_value, "ready");
+
+        let apc_2 = apc.clone();
+        let waiting = std::thread::spawn(move || async_test!(apc_2.wait_for(&["neverKey"], time::Duration::from_secs(10))));
+        std::thread::sleep(time::Duration::from_millis(100));
+        apc.close();
+        assert!(matches!(waiting.join().unwrap(), Err(client::error::ApolloError::Closed)));
+
+        //必选namespace不存在时启动失败
+        let conn_future = client::apollo_config_client::new_with_optional_namespaces(vec![&meta_srv_addr], APP_ID, CLUSTER, vec!["notExists"], vec![NS_NS1], None);
+        assert!(matches!(async_test!(conn_future).err(), Some(client::error::ApolloError::NamespaceNotFound { .. })));
+    }
+
+    #[test]
+    fn lazy_load_namespace() {
+        use client::apollo_config_client::ApolloLazyLoad;
+
+        let server = apollo_server();
+        server.set(APP_ID, CLUSTER, "lazy", "lazyKey", "lazyValue");
+        server.publish(APP_ID, CLUSTER, "lazy");
+        let meta_srv_addr = server.url();
+        let apc = async_test!(client::apollo_config_client::new(vec![&meta_srv_addr], APP_ID, CLUSTER, Some(vec![NS_NS1]), None)).unwrap();
+
+        //默认不加载
+        assert!(apc.get_config_from_namespace(KEY, NS_NS2).is_none());
+        assert_eq!(apc.namespace_order().len(), 1);
+
+        apc.set_lazy_load(ApolloLazyLoad::Wait(time::Duration::from_secs(5)));
+        assert_eq!(apc.get_config_from_namespace(KEY, NS_NS2).unwrap().config_value, "9090");
+        assert!(apc.get_config_from_namespace(KEY, "notExists").is_none());
+        assert!(apc.status().namespaces.iter().all(|ns| ns.namespace != "notExists"));
+
+        apc.set_lazy_load(ApolloLazyLoad::Background);
+        let mut value = apc.get_config_from_namespace("lazyKey", "lazy");
+        assert!(value.is_none());
+        for _ in 0..100 {
+            value = apc.get_config_from_namespace("lazyKey", "lazy");
+            if value.is_some() {
+                break;
+            }
+            std::thread::sleep(time::Duration::from_millis(50));
+        }
+        assert_eq!(value.unwrap().config_value, "lazyValue");
+        assert_eq!(apc.namespace_order().len(), 3);
+        apc.close();
+    }
+
+    #[test]
+    fn debounced_subscriber() {
+        use crate::client::{testing::FakeApolloConfig, apollo_config_client::ApolloChangeAction};
+
+        let fake = FakeApolloConfig::new(APP_ID, CLUSTER);
+        fake.set_and_publish(NS_NS1, KEY, "100");
+        let mut rx = fake.client().subscribe_debounced(time::Duration::from_millis(200));
+        let mut raw = fake.client().subscribe();
+
+        fake.set_and_publish(NS_NS1, "tmp", "1");
+        fake.set_and_publish(NS_NS1, "k2", "2");
+        fake.remove(NS_NS1, "tmp");
+        fake.publish(NS_NS1);
+        fake.set_and_publish(NS_NS1, KEY, "300");
+
+        let mut raw_count = 0;
+        while raw.try_recv().is_ok() {
+            raw_count += 1;
+        }
+        assert_eq!(raw_count, 4);
+
+        let events = rx.blocking_recv().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!((events[0].key.as_str(), events[0].new_value.as_str()), ("k2", "2"));
+        assert!(matches!(events[0].action, ApolloChangeAction::ADD));
+        assert_eq!((events[1].key.as_str(), events[1].new_value.as_str()), (KEY, "300"));
+        assert!(matches!(events[1].action, ApolloChangeAction::UPDATE));
+        std::thread::sleep(time::Duration::from_millis(300));
+        assert!(rx.try_recv().is_err());
+
+        //返回的receiver drop后转发任务退出, 不再订阅源
+        let (tx, _rx) = tokio::sync::broadcast::channel::<Vec<client::apollo_config_client::ApolloChangeEvent>>(10);
+        let debounced = client::source::debounce(tx.subscribe(), time::Duration::from_millis(200));
+        assert_eq!(tx.receiver_count(), 2);
+        drop(debounced);
+        for _ in 0..100 {
+            if tx.receiver_count() == 1 {
+                break;
+            }
+            std::thread::sleep(time::Duration::from_millis(20));
+        }
+        assert_eq!(tx.receiver_count(), 1);
+
+        //真实client在监听线程的runtime中转发
+        let server = apollo_server();
+        let meta_srv_addr = server.url();
+        let apc = async_test!(client::apollo_config_client::new(vec![&meta_srv_addr], APP_ID, CLUSTER, Some(vec![NS_NS1]), None)).unwrap();
+        let mut rx = apc.subscribe_debounced(time::Duration::from_millis(200));
+        server.set(APP_ID, CLUSTER, NS_NS1, KEY, "500");
+        server.publish(APP_ID, CLUSTER, NS_NS1);
+        let events = recv_timeout(&mut rx);
+        assert_eq!((events[0].key.as_str(), events[0].new_value.as_str()), (KEY, "500"));
+        apc.close();
+    }
+
+    #[test]
+    fn release_validator() {
+        use crate::client::{testing::FakeApolloConfig, apollo_config_client::ApolloChangeAction};
+
+        let server = apollo_server();
+        let meta_srv_addr = server.url();
+        let apc = async_test!(client::apollo_config_client::new(vec![&meta_srv_addr], APP_ID, CLUSTER, Some(vec![NS_NS1]), None)).unwrap();
+        apc.add_key_validator(Some(NS_NS1), KEY, |value| value.parse::<u64>().map(|_| ()).map_err(|err| err.to_string()));
+        let mut rx = apc.subscribe();
+        let mut rejections = apc.subscribe_rejections();
+
+        server.set(APP_ID, CLUSTER, NS_NS1, KEY, "abc");
+        server.publish(APP_ID, CLUSTER, NS_NS1);
+        let rejection = recv_timeout(&mut rejections);
+        assert_eq!(rejection.namespace, NS_NS1);
+        assert!(rejection.reason.contains(KEY));
+        assert!(rx.try_recv().is_err());
+        assert_eq!(apc.get_config(KEY).unwrap().config_value, "100");
+        let status = apc.status();
+        assert_eq!(status.namespaces[0].rejected_release_key.as_ref(), Some(&rejection.release_key));
+        assert!(status.namespaces[0].last_error.as_ref().unwrap().contains(KEY));
+
+        server.set(APP_ID, CLUSTER, NS_NS1, KEY, "200");
+        server.publish(APP_ID, CLUSTER, NS_NS1);
+        let events = recv_timeout(&mut rx);
+        assert!(matches!(events[0].action, ApolloChangeAction::UPDATE));
+        assert_eq!(apc.get_config(KEY).unwrap().config_value, "200");
+        assert!(apc.status().namespaces[0].rejected_release_key.is_none());
+        apc.close();
+
+        let fake = FakeApolloConfig::new(APP_ID, CLUSTER);
+        fake.client().add_release_validator(None, |release| {
+            if release.configurations.len() > 1 {
+                return Err(format!("too many keys in {}", release.namespace));
+            }
+            Ok(())
+        });
+        let mut rejections = fake.client().subscribe_rejections();
+        fake.set_and_publish(NS_NS1, KEY, "100");
+        fake.take_events();
+        let rk = fake.set_and_publish(NS_NS1, "other", "1");
+        assert!(fake.client().get_config("other").is_none());
+        assert!(fake.take_events().is_empty());
+        assert_eq!(rejections.try_recv().unwrap().release_key, rk);
+    }
+
+    #[test]
+    fn freeze_namespace() {
+        use crate::client::testing::FakeApolloConfig;
+
+        let server = apollo_server();
+        //长轮询不会自己返回, 解冻后需要立即重新发起
+        server.set_long_poll_timeout(time::Duration::from_secs(30));
+        let meta_srv_addr = server.url();
+        let apc = async_test!(client::apollo_config_client::new(vec![&meta_srv_addr], APP_ID, CLUSTER, Some(vec![NS_NS1, NS_NS2]), None)).unwrap();
+        assert!(!apc.freeze_namespace("notListened", None));
+        assert!(!apc.unfreeze_namespace(NS_NS1));
+        assert!(apc.freeze_namespace(NS_NS1, None));
+        let ns1_status = |apc: &client::apollo_config_client::ApolloConfigClient| apc.status().namespaces.into_iter().find(|ns| ns.namespace == NS_NS1).unwrap();
+        let frozen = ns1_status(&apc);
+        assert_eq!(frozen.frozen_release_key.as_ref(), Some(&frozen.release_key));
+
+        let mut rx = apc.subscribe();
+        server.set(APP_ID, CLUSTER, NS_NS1, "frozenKey", "1");
+        let skipped = server.publish(APP_ID, CLUSTER, NS_NS1);
+        server.set(APP_ID, CLUSTER, NS_NS2, "ns2Key", "2");
+        server.publish(APP_ID, CLUSTER, NS_NS2);
+        let events = recv_timeout(&mut rx);
+        assert!(events.iter().all(|e| e.namespace == NS_NS2));
+        assert!(apc.get_config("frozenKey").is_none());
+        assert_eq!(ns1_status(&apc).release_key, frozen.release_key);
+
+        //冻结在被跳过的发布上, 立即拉取并停留在该版本
+        assert!(apc.freeze_namespace(NS_NS1, Some(&skipped)));
+        let res = async_test!(apc.wait_for(&["frozenKey"], time::Duration::from_secs(3)));
+        assert!(res.is_ok());
+        assert_eq!(ns1_status(&apc).release_key, skipped);
+        server.set(APP_ID, CLUSTER, NS_NS1, "frozenKey", "2");
+        server.publish(APP_ID, CLUSTER, NS_NS1);
+        std::thread::sleep(time::Duration::from_millis(500));
+        assert_eq!(apc.get_config("frozenKey").unwrap().config_value, "1");
+        assert_eq!(ns1_status(&apc).frozen_release_key, Some(skipped));
+
+        //解冻后立即追上最新发布, 不等待进行中的长轮询
+        let mut rx = apc.subscribe();
+        assert!(apc.unfreeze_namespace(NS_NS1));
+        let events = recv_timeout(&mut rx);
+        assert!(events.iter().any(|e| e.key == "frozenKey" && e.new_value == "2"));
+        assert!(ns1_status(&apc).frozen_release_key.is_none());
+        apc.close();
+
+        //冻结在之后的版本时, 跳过其他发布直到该版本
+        let fake = FakeApolloConfig::new(APP_ID, CLUSTER);
+        fake.set_and_publish(NS_NS1, KEY, "1");
+        assert!(fake.client().freeze_namespace(NS_NS1, Some("fake-release-3")));
+        fake.set_and_publish(NS_NS1, KEY, "2");
+        assert_eq!(fake.client().get_config(KEY).unwrap().config_value, "1");
+        fake.set_and_publish(NS_NS1, KEY, "3");
+        assert_eq!(fake.client().get_config(KEY).unwrap().config_value, "3");
+        fake.set_and_publish(NS_NS1, KEY, "4");
+        assert_eq!(fake.client().get_config(KEY).unwrap().config_value, "3");
+        assert_eq!(fake.client().status().namespaces[0].frozen_release_key.as_deref(), Some("fake-release-3"));
+    }
+
+    #[test]
+    fn unlisten_namespace() {
+        let server = apollo_server();
+        server.set(APP_ID, CLUSTER, NS_NS2, "ns2Only", "1");
+        server.publish(APP_ID, CLUSTER, NS_NS2);
+        let meta_srv_addr = server.url();
+        let apc = async_test!(client::apollo_config_client::new(vec![&meta_srv_addr], APP_ID, CLUSTER, Some(vec![NS_NS1, NS_NS2]), None)).unwrap();
+        assert_eq!(apc.get_config(KEY).unwrap().config_value, "9090");
+        assert!(!apc.unlisten_namespace("notListened"));
+
+        let mut rx = apc.subscribe();
+        assert!(apc.unlisten_namespace(NS_NS2));
+        assert!(!apc.unlisten_namespace(NS_NS2));
+        assert_eq!(apc.namespace_order(), vec![(NS_NS1.to_string(), 0)]);
+        assert_eq!(apc.get_config(KEY).unwrap().config_value, "100");
+        assert!(apc.get_config("ns2Only").is_none());
+        assert!(apc.get_config_from_namespace(KEY, NS_NS2).is_none());
+        let events = rx.try_recv().unwrap();
+        assert!(events.iter().any(|e| e.key == "ns2Only" && matches!(e.action, client::apollo_config_client::ApolloChangeAction::DELETE)));
+
+        //之后的长轮询不再带上该namespace
+        let mut polled = Vec::new();
+        for _ in 0..50 {
+            polled = server.polled_namespaces(APP_ID, CLUSTER);
+            if !polled.is_empty() && !polled.contains(&NS_NS2.to_string()) {
+                break;
+            }
+            std::thread::sleep(time::Duration::from_millis(100));
+        }
+        assert_eq!(polled, vec![NS_NS1.to_string()]);
+        apc.close();
+    }
+
+    #[test]
+    fn namespace_priority() {
+        use client::apollo_config_client::ApolloChangeAction;
+
+        let server = apollo_server();
+        server.set(APP_ID, CLUSTER, "ns3", KEY, "300");
+        server.publish(APP_ID, CLUSTER, "ns3");
+        let meta_srv_addr = server.url();
+        let apc = async_test!(client::apollo_config_client::new(vec![&meta_srv_addr], APP_ID, CLUSTER, Some(vec![NS_NS1, NS_NS2]), None)).unwrap();
+        //优先级相同时后监听的优先
+        assert_eq!(apc.get_config(KEY).unwrap().config_value, "9090");
+        assert_eq!(apc.namespace_order(), vec![(NS_NS2.to_string(), 0), (NS_NS1.to_string(), 0)]);
+
+        let mut rx = apc.subscribe();
+        assert!(apc.set_namespace_priority(NS_NS1, 10));
+        assert!(!apc.set_namespace_priority("notListened", 10));
+        let item = apc.get_config(KEY).unwrap();
+        assert_eq!((item.config_value.as_str(), item.namespace.as_str()), ("100", NS_NS1));
+        let events = rx.try_recv().unwrap();
+        assert_eq!((events[0].key.as_str(), events[0].new_value.as_str()), (KEY, "100"));
+        assert!(matches!(events[0].action, ApolloChangeAction::UPDATE));
+
+        assert!(async_test!(apc.listen_namespace_with_priority("ns3", 5)).is_none());
+        assert_eq!(apc.namespace_order(), vec![(NS_NS1.to_string(), 10), ("ns3".to_string(), 5), (NS_NS2.to_string(), 0)]);
+        assert_eq!(apc.get_config(KEY).unwrap().config_value, "100");
+
+        assert!(apc.set_namespace_priority(NS_NS1, -1));
+        assert_eq!(apc.namespace_order(), vec![("ns3".to_string(), 5), (NS_NS2.to_string(), 0), (NS_NS1.to_string(), -1)]);
+        assert_eq!(apc.get_config(KEY).unwrap().config_value, "300");
+        //指定namespace取值不受优先级影响
+        assert_eq!(apc.get_config_from_namespace(KEY, NS_NS1).unwrap().config_value, "100");
+        apc.close();
+    }
+
+    #[test]
+    fn enumerate_keys() {
+        use crate::client::testing::FakeApolloConfig;
+
+        let fake = FakeApolloConfig::new(APP_ID, CLUSTER);
+        let acc = fake.client();
+        fake.set(NS_NS1, KEY, "100");
+        fake.set(NS_NS1, "db.host", "localhost");
+        fake.publish(NS_NS1);
+        fake.set(NS_NS2, KEY, "200");
+        fake.set(NS_NS2, "db.port", "3306");
+        fake.set(NS_NS2, "cache.size", "10");
+        fake.publish(NS_NS2);
+
+        assert_eq!(acc.keys(), vec!["cache.size", "db.host", "db.port", KEY]);
+        assert_eq!(acc.keys_in_namespace(NS_NS1).unwrap(), vec!["db.host", KEY]);
+        assert_eq!(acc.keys_in_namespace(NS_NS2).unwrap(), vec!["cache.size", "db.port", KEY]);
+        assert!(acc.keys_in_namespace("notListened").is_none());
+
+        let db = acc.get_by_prefix("db.");
+        assert_eq!(db.len(), 2);
+        assert_eq!(db["db.host"].namespace, NS_NS1);
+        assert_eq!(db["db.port"].config_value, "3306");
+        assert!(acc.get_by_prefix("none.").is_empty());
+
+        let snapshot = acc.snapshot();
+        assert_eq!(snapshot.len(), 4);
+        assert_eq!((snapshot[KEY].config_value.as_str(), snapshot[KEY].namespace.as_str()), ("200", NS_NS2));
+        acc.set_namespace_priority(NS_NS1, 1);
+        let snapshot = acc.snapshot();
+        assert_eq!((snapshot[KEY].config_value.as_str(), snapshot[KEY].namespace.as_str()), ("100", NS_NS1));
+    }
+
+    #[test]
+    fn override_layers() {
+        use crate::client::{testing::FakeApolloConfig, apollo_config_client::ApolloConfigLayer};
+
+        let fake = FakeApolloConfig::new(APP_ID, CLUSTER);
+        let acc = fake.client();
+        fake.set(NS_NS1, "db.host", "apollo-host");
+        fake.set(NS_NS1, "db.port", "3306");
+        fake.set(NS_NS1, KEY, "100");
+        fake.publish(NS_NS1);
+
+        let path = std::env::temp_dir().join(format!("apollo-layers-{}.properties", std::process::id()));
+        std::fs::write(&path, "db.host=file-host\ndb.port=3307\n").unwrap();
+        let path = path.to_str().unwrap().to_string();
+        assert!(acc.set_override_file(Some(&path)).is_none());
+        let _env = ENV_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        std::env::set_var("APOLLO_SDK_LAYER_TEST_DB_HOST", "env-host");
+        std::env::set_var("APOLLO_SDK_LAYER_TEST_ONLY_ENV", "ignored");
+        acc.set_env_override(Some("APOLLO_SDK_LAYER_TEST_"));
+
+        //环境变量 > 本地覆盖文件 > Apollo
+        let item = acc.get_config("db.host").unwrap();
+        assert_eq!((item.config_value.as_str(), item.namespace.as_str()), ("env-host", "APOLLO_SDK_LAYER_TEST_DB_HOST"));
+        assert_eq!(item.layer, ApolloConfigLayer::Env);
+        let item = acc.get_config("db.port").unwrap();
+        assert_eq!((item.config_value.as_str(), item.namespace.as_str()), ("3307", path.as_str()));
+        assert_eq!(item.layer, ApolloConfigLayer::OverrideFile);
+        let item = acc.get_config(KEY).unwrap();
+        assert_eq!((item.config_value.as_str(), item.namespace.as_str()), ("100", NS_NS1));
+        assert_eq!(item.layer, ApolloConfigLayer::Apollo);
+        //只覆盖已存在的key
+        assert!(acc.get_config("only.env").is_none());
+        //指定namespace时只取Apollo的值
+        assert_eq!(acc.get_config_from_namespace("db.host", NS_NS1).unwrap().config_value, "apollo-host");
+
+        acc.set_env_override(None);
+        let item = acc.get_config("db.host").unwrap();
+        assert_eq!((item.config_value.as_str(), item.layer), ("file-host", ApolloConfigLayer::OverrideFile));
+        assert!(acc.set_override_file(None).is_none());
+        let item = acc.get_config("db.host").unwrap();
+        assert_eq!((item.config_value.as_str(), item.layer), ("apollo-host", ApolloConfigLayer::Apollo));
+        assert!(acc.set_override_file(Some("/not/exists.properties")).is_some());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn fake_config_events_and_listen() {
+        use crate::client::{testing::FakeApolloConfig, apollo_config_client::ApolloNamespaceSource};
+
+        let fake = FakeApolloConfig::new(APP_ID, CLUSTER);
+        for i in 0..40 {
+            fake.set_and_publish(NS_NS1, &format!("key{}", i), "v");
+        }
+        //超过broadcast容量也不丢失事件
+        assert_eq!(fake.take_events().len(), 40);
+        assert!(fake.take_events().is_empty());
+
+        //未发布的namespace作为空namespace监听, 之后可以发布
+        let acc = fake.client();
+        assert!(async_test!(acc.listen_namespace_with_priority("other", 5)).is_none());
+        assert_eq!(acc.namespace_order(), vec![("other".to_string(), 5), (NS_NS1.to_string(), 0)]);
+        let status = acc.status();
+        assert_eq!(status.namespaces[0].source, ApolloNamespaceSource::NotFound);
+        assert_eq!(acc.keys_in_namespace("other"), Some(Vec::new()));
+
+        fake.set_and_publish("other", "key0", "other");
+        assert_eq!(acc.get_config("key0").unwrap().config_value, "other");
+        let status = acc.status();
+        assert_eq!(status.namespaces[0].source, ApolloNamespaceSource::Remote);
+        assert_eq!(status.namespaces[0].priority, 5);
+        assert!(status.namespaces[0].last_error.is_none());
+    }
+
+    #[test]
+    fn properties_file_watch() {
+        use crate::client::source::{ConfigSource, PropertiesFileSource};
+
+        let path = std::env::temp_dir().join(format!("apollo-watch-{}.properties", std::process::id()));
+        std::fs::write(&path, "timeout=200\n").unwrap();
+        let file = PropertiesFileSource::open(path.to_str().unwrap()).unwrap();
+        let mut rx = file.subscribe();
+
+        //不读取配置, 由后台轮询发现文件修改
+        std::fs::write(&path, "timeout=300\n").unwrap();
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap() + time::Duration::from_secs(10);
+        std::fs::File::options().write(true).open(&path).unwrap().set_modified(modified).unwrap();
+        let mut events = None;
+        for _ in 0..50 {
+            if let Ok(res) = rx.try_recv() {
+                events = Some(res);
+                break;
+            }
+            std::thread::sleep(time::Duration::from_millis(100));
+        }
+        let events = events.unwrap();
+        assert_eq!((events[0].key.as_str(), events[0].new_value.as_str()), (KEY, "300"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(all(feature = "config", feature = "figment"))]
+    #[test]
+    fn adapters_not_tracked() {
+        use crate::client::{testing::FakeApolloConfig, config_rs::ApolloSource, figment::ApolloProvider};
+
+        let fake = FakeApolloConfig::new(APP_ID, CLUSTER);
+        fake.set(NS_NS1, KEY, "100");
+        fake.set(NS_NS1, "unused.key", "x");
+        fake.publish(NS_NS1);
+        let acc = fake.client();
+        acc.enable_access_tracking(true);
+
+        //整体读取namespace不计入统计
+        let cfg = config::Config::builder().add_source(ApolloSource::with_namespaces(acc.clone(), &[NS_NS1])).build().unwrap();
+        assert_eq!(cfg.get::<u32>(KEY).unwrap(), 100);
+        let figment = ::figment::Figment::from(ApolloProvider::namespace(acc.clone(), NS_NS1));
+        assert_eq!(figment.extract_inner::<String>("unused.key").unwrap(), "x");
+
+        let report = acc.access_report();
+        assert!(report.accessed.is_empty());
+        assert_eq!(report.unused.len(), 2);
+    }
+
+    #[cfg(feature = "blocking")]
+    #[test]
+    fn blocking_client_subscribe() {
+        let server = apollo_server();
+        let meta_srv_addr = server.url();
+        let bc = client::blocking::new(vec![&meta_srv_addr], APP_ID, CLUSTER, Some(vec![NS_NS1]), None).unwrap();
+        assert!(bc.wait_for(&[KEY], time::Duration::from_secs(1)).is_ok());
+        let mut rx = bc.subscribe();
+        let mut debounced = bc.subscribe_debounced(time::Duration::from_millis(100));
+
+        server.set(APP_ID, CLUSTER, NS_NS1, KEY, "200");
+        server.publish(APP_ID, CLUSTER, NS_NS1);
+        let (mut events, mut merged) = (None, None);
+        for _ in 0..100 {
+            if events.is_none() {
+                events = rx.try_recv().ok();
+            }
+            if merged.is_none() {
+                merged = debounced.try_recv().ok();
+            }
+            if events.is_some() && merged.is_some() {
+                break;
+            }
+            std::thread::sleep(time::Duration::from_millis(50));
+        }
+        assert_eq!(events.unwrap()[0].new_value, "200");
+        assert_eq!(merged.unwrap()[0].new_value, "200");
+        bc.close();
+    }
+
+    #[test]
+    fn namespace_load_failures() {
+        use client::error::ApolloError;
+
+        let server = apollo_server();
+        let meta_srv_addr = server.url();
+        let apc = async_test!(client::apollo_config_client::new(vec![&meta_srv_addr], APP_ID, CLUSTER, Some(vec![NS_NS1]), None)).unwrap();
+
+        //namespace不存在
+        assert!(matches!(async_test!(apc.listen_namespace("missing")), Some(ApolloError::NamespaceNotFound { .. })));
+        assert!(apc.status().namespaces.iter().all(|ns| ns.namespace != "missing"));
+
+        //容忍不存在的namespace, 发布前不会反复拉取
+        apc.tolerate_missing_namespace(true);
+        assert!(async_test!(apc.listen_namespace("later")).is_none());
+        let later = apc.status().namespaces.into_iter().find(|ns| ns.namespace == "later").unwrap();
+        assert_eq!(later.source, client::apollo_config_client::ApolloNamespaceSource::NotFound);
+        std::thread::sleep(time::Duration::from_millis(2500));
+        assert_eq!(server.config_fetches(APP_ID, CLUSTER, "later"), 1);
+        server.set(APP_ID, CLUSTER, "later", "laterKey", "ready");
+        server.publish(APP_ID, CLUSTER, "later");
+        assert!(async_test!(apc.wait_for(&["laterKey"], time::Duration::from_secs(10))).is_ok());
+
+        //已发布的namespace拉取返回404/403, 不重试, 保留原配置
+        for status in [404, 403] {
+            server.fail_configs(APP_ID, CLUSTER, NS_NS1, Some(status));
+            let fetches = server.config_fetches(APP_ID, CLUSTER, NS_NS1);
+            server.set(APP_ID, CLUSTER, NS_NS1, KEY, &status.to_string());
+            server.publish(APP_ID, CLUSTER, NS_NS1);
+            std::thread::sleep(time::Duration::from_millis(2500));
+            assert_eq!(server.config_fetches(APP_ID, CLUSTER, NS_NS1), fetches + 1);
+            let ns1 = apc.status().namespaces.into_iter().find(|ns| ns.namespace == NS_NS1).unwrap();
+            assert!(ns1.last_error.is_some());
+            assert_eq!(apc.get_config(KEY).unwrap().config_value, "100");
+        }
+
+        //5xx按退避重试, 恢复后拉取最新配置
+        server.fail_configs(APP_ID, CLUSTER, NS_NS1, Some(503));
+        let fetches = server.config_fetches(APP_ID, CLUSTER, NS_NS1);
+        server.set(APP_ID, CLUSTER, NS_NS1, KEY, "503");
+        server.publish(APP_ID, CLUSTER, NS_NS1);
+        std::thread::sleep(time::Duration::from_millis(3500));
+        let retries = server.config_fetches(APP_ID, CLUSTER, NS_NS1) - fetches;
+        assert!((2..=4).contains(&retries), "retries: {}", retries);
+        let mut rx = apc.subscribe();
+        server.fail_configs(APP_ID, CLUSTER, NS_NS1, None);
+        let events = recv_timeout(&mut rx);
+        assert!(events.iter().any(|ev| ev.key == KEY && ev.new_value == "503"));
+        apc.close();
+
+        //未签名的请求返回401
+        server.set_secret(APP_ID, SECERT);
+        let res = async_test!(client::apollo_config_client::new(vec![&meta_srv_addr], APP_ID, CLUSTER, Some(vec![NS_NS1]), None));
+        assert!(matches!(res.err(), Some(ApolloError::Unauthorized { status: 401, .. })));
+    }
+
+    #[test]
+    fn notification_messages_echoed() {
+        let server = apollo_server();
+        let meta_srv_addr = server.url();
+        let apc = async_test!(client::apollo_config_client::new(vec![&meta_srv_addr], APP_ID, CLUSTER, Some(vec![NS_NS1]), None)).unwrap();
+        assert!(server.fetched_messages(APP_ID, CLUSTER, NS_NS1).is_none());
+
+        let mut rx = apc.subscribe();
+        server.set(APP_ID, CLUSTER, NS_NS1, KEY, "200");
+        server.publish(APP_ID, CLUSTER, NS_NS1);
+        recv_timeout(&mut rx);
+        let notification_id = apc.status().namespaces.into_iter().find(|ns| ns.namespace == NS_NS1).unwrap().notification_id;
+        let message_key = format!("{}+{}+{}", APP_ID, CLUSTER, NS_NS1);
+
+        //拉取配置时带上通知中的messages
+        let fetched = server.fetched_messages(APP_ID, CLUSTER, NS_NS1).unwrap();
+        assert_eq!(fetched["details"][&message_key], notification_id);
+
+        //下一次长轮询同样带上
+        let mut polled = None;
+        for _ in 0..100 {
+            polled = server.polled_messages(APP_ID, CLUSTER, NS_NS1);
+            if polled.is_some() {
+                break;
+            }
+            std::thread::sleep(time::Duration::from_millis(50));
+        }
+        assert_eq!(polled.unwrap(), fetched);
+        apc.close();
+    }
+
+    #[test]
+    fn namespace_change_events() {
+        use crate::client::{testing::FakeApolloConfig, apollo_config_client::ApolloChangeAction};
+
+        let fake = FakeApolloConfig::new(APP_ID, CLUSTER);
+        fake.set_and_publish(NS_NS1, KEY, "100");
+        fake.set_and_publish(NS_NS2, KEY, "9090");
+        fake.client().set_namespace_priority(NS_NS2, 10);
+        let mut effective = fake.client().subscribe();
+        let mut namespaces = fake.client().subscribe_namespaces();
+
+        //被遮盖的key变更时生效值不变, 只通知namespace的订阅者
+        fake.set_and_publish(NS_NS1, KEY, "200");
+        assert!(effective.try_recv().is_err());
+        let events = namespaces.try_recv().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].namespace.as_str(), events[0].key.as_str(), events[0].new_value.as_str()), (NS_NS1, KEY, "200"));
+        assert!(matches!(events[0].action, ApolloChangeAction::UPDATE));
+
+        //调整优先级不改变namespace自身的配置
+        fake.client().set_namespace_priority(NS_NS2, -1);
+        assert_eq!(effective.try_recv().unwrap()[0].new_value, "200");
+        assert!(namespaces.try_recv().is_err());
+
+        fake.client().unlisten_namespace(NS_NS2);
+        let events = namespaces.try_recv().unwrap();
+        assert_eq!((events[0].namespace.as_str(), events[0].key.as_str()), (NS_NS2, KEY));
+        assert!(matches!(events[0].action, ApolloChangeAction::DELETE));
+    }
 }